@@ -3,14 +3,32 @@ use std::{
     io::Write,
 };
 
+#[allow(dead_code)]
+#[path = "src/protocol.rs"]
+mod protocol;
+
 macro_rules! feature_dm_file {
     ($name:expr) => {
         &"dmsrc/{}.dm".replace("{}", $name)
     };
 }
 
+/// Line in `main.dm` that is replaced with the return protocol's `#define`s
+const PROTOCOL_PLACEHOLDER: &str =
+    "// RETURN PROTOCOL DEFINES (generated from src/protocol.rs by build.rs)\n";
+
 fn main() {
     let mut f = File::create("target/rustick.dm").unwrap();
 
-    writeln!(f, "{}", read_to_string(feature_dm_file!("main")).unwrap()).unwrap();
+    let main = read_to_string(feature_dm_file!("main")).unwrap();
+    assert!(
+        main.contains(PROTOCOL_PLACEHOLDER),
+        "main.dm is missing the return protocol placeholder"
+    );
+    writeln!(
+        f,
+        "{}",
+        main.replace(PROTOCOL_PLACEHOLDER, &protocol::dm_defines())
+    )
+    .unwrap();
 }
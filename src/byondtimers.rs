@@ -1,17 +1,19 @@
 use crate::core::*;
+use crate::dispatch::{self, DispatchMode};
 use crate::timer::*;
 use hierarchical_hash_wheel_timer::*;
 use meowtonin::{ByondError, ByondResult, ByondValue, byond_fn};
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::{LazyLock, Mutex};
 use std::time::Duration;
 use uuid::Uuid;
 
-type TimerCoreType = TimerWithThread<Uuid, OneShotClosureState<Uuid>, PeriodicClosureState<Uuid>>;
-type TimerRefType = TimerRef<Uuid, OneShotClosureState<Uuid>, PeriodicClosureState<Uuid>>;
+type TimerCoreType = TimerWithThread<Uuid, LateClosureState<Uuid>, AdaptiveClosureState<Uuid>>;
+type TimerRefType = TimerRef<Uuid, LateClosureState<Uuid>, AdaptiveClosureState<Uuid>>;
 
 pub static BYOND_TIMER_CORE: LazyLock<Option<TimerCoreType>> =
     LazyLock::new(
-        || match TimerWithThread::for_uuid_closures_sans_autotick() {
+        || match TimerWithThread::for_uuid_adaptive_closures_sans_autotick() {
             Ok(timer) => Some(timer),
             Err(e) => {
                 log_error(format!("failed to start BYOND-tick timer thread: {e}"));
@@ -19,28 +21,37 @@ pub static BYOND_TIMER_CORE: LazyLock<Option<TimerCoreType>> =
             }
         },
     );
-pub static BYOND_TIMER: LazyLock<Mutex<Option<TimerRefType>>> =
-    LazyLock::new(|| Mutex::new(BYOND_TIMER_CORE.as_ref().map(TimerWithThread::timer_ref)));
+pub static BYOND_TIMER: LazyLock<Mutex<Option<TimerRefType>>> = LazyLock::new(|| {
+    Mutex::new(BYOND_TIMER_CORE.as_ref().map(|core| {
+        let mut timers = core.timer_ref();
+        timers.on_cancel(dispatch::forget_due);
+        timers
+    }))
+});
+/// How many times [`tick_byondtick`] advanced the BYOND-tick timers, see [`get_tick_count`]
+pub static BYOND_TICK: AtomicU32 = AtomicU32::new(0);
 
 /// Schedules a one-shot timer based on BYOND ticks.
 ///
 /// # Arguments
-/// * `delay` - Number of BYOND ticks to wait before executing
+/// * `delay` - Number of BYOND ticks to wait before executing, or a duration string like `"5m30s"`, see [`Delay`]
 /// * `owning_obj` - The BYOND object that owns the proc to call
 /// * `proc_path` - The path to the proc to call
 /// * `proc_args` - Arguments to pass to the proc
+/// * `options` - Optional associative list of extra settings, see [`TimerOptions`]
 ///
 /// # Returns
 /// * A UUID string identifying the timer for cancellation
 #[byond_fn]
 pub fn schedule_once_tick(
-    delay: u64,
+    delay: Delay,
     owning_obj: ByondValue,
     proc_path: ByondValue,
     proc_args: ByondValue,
+    options: TimerOptions,
 ) -> ByondResult<String> {
-    let id = get_uuid(TimerType::ByondTick);
-    let delay = Duration::from_millis(delay);
+    let id = options.id(TimerType::ByondTick);
+    let delay = delay.ticks();
 
     if owning_obj.is_null() || proc_path.is_null() {
         return Err(ByondError::InvalidProc);
@@ -59,33 +70,122 @@ pub fn schedule_once_tick(
         return Err(ByondError::InvalidProc);
     };
 
-    schedule_oneshot_timer(timers, id, delay, owning_obj, proc_path, proc_args);
+    schedule_oneshot_timer(timers, id, delay, owning_obj, proc_path, proc_args, options);
 
     Ok(id.to_string())
 }
 
+/// Schedules a one-shot timer that fires on an absolute BYOND tick, as counted by [`get_tick_count`].
+///
+/// # Arguments
+/// * `target_tick` - The tick to fire on, ticks that already passed fire on the next tick, see [`ticks_until`]
+/// * `owning_obj` - The BYOND object that owns the proc to call
+/// * `proc_path` - The path to the proc to call
+/// * `proc_args` - Arguments to pass to the proc
+/// * `options` - Optional associative list of extra settings, see [`TimerOptions`]
+///
+/// # Returns
+/// * A UUID string identifying the timer for cancellation
+#[byond_fn]
+pub fn schedule_at_tick(
+    target_tick: u32,
+    owning_obj: ByondValue,
+    proc_path: ByondValue,
+    proc_args: ByondValue,
+    options: TimerOptions,
+) -> ByondResult<String> {
+    let delay = ticks_until(target_tick, BYOND_TICK.load(Ordering::Acquire));
+    schedule_once_tick(
+        Delay::Units(delay),
+        owning_obj,
+        proc_path,
+        proc_args,
+        options,
+    )
+}
+
+/// The delay of a timer that fires on `target_tick`, with the tick count at `tick_count`.
+///
+/// A timer with a delay of `n` ticks fires on the `n`th tick after it was scheduled. Ticks that already
+/// passed, the current one included, fire on the next tick rather than right away on the timer thread.
+pub fn ticks_until(target_tick: u32, tick_count: u32) -> u64 {
+    u64::from(target_tick.saturating_sub(tick_count).max(1))
+}
+
+/// The tick on which `world.time` reaches `target_time`, with `world.time` at `time` and the tick count at `tick_count`.
+///
+/// The times are in deciseconds, like `tick_lag`. The ticks left are rounded up so that the timer never fires
+/// early, times that already passed come out as the current tick.
+pub fn world_time_tick(target_time: f32, time: f32, tick_lag: f32, tick_count: u32) -> u32 {
+    let tick_lag = if tick_lag > 0.0 { tick_lag } else { 1.0 };
+    // shaves off the rounding errors of f32, so that a whole number of ticks isn't rounded up to one more
+    let ticks = ((target_time - time) / tick_lag - 1e-3).ceil();
+    // negative and NaN tick counts come out as 0
+    tick_count.saturating_add(ticks as u32)
+}
+
+/// Schedules a one-shot timer that fires once `world.time` reaches a game time.
+///
+/// The time left is converted to ticks by the current `world.tick_lag`, rounding up, so the timer
+/// fires on the first tick at or after the target as long as the tick rate doesn't change meanwhile.
+///
+/// # Arguments
+/// * `target_time` - The `world.time` to fire at, in deciseconds. Times that already passed fire on the next tick,
+///   like the ticks of [`schedule_at_tick`]
+/// * `owning_obj` - The BYOND object that owns the proc to call
+/// * `proc_path` - The path to the proc to call
+/// * `proc_args` - Arguments to pass to the proc
+/// * `options` - Optional associative list of extra settings, see [`TimerOptions`]
+///
+/// # Returns
+/// * A UUID string identifying the timer for cancellation
+#[byond_fn]
+pub fn schedule_at_world_time(
+    target_time: f32,
+    owning_obj: ByondValue,
+    proc_path: ByondValue,
+    proc_args: ByondValue,
+    options: TimerOptions,
+) -> ByondResult<String> {
+    let world = ByondValue::world();
+    let time: f32 = world.read_var("time")?;
+    let tick_lag: f32 = world.read_var("tick_lag").unwrap_or(1.0);
+    let tick_count = BYOND_TICK.load(Ordering::Acquire);
+    let target_tick = world_time_tick(target_time, time, tick_lag, tick_count);
+    schedule_once_tick(
+        Delay::Units(ticks_until(target_tick, tick_count)),
+        owning_obj,
+        proc_path,
+        proc_args,
+        options,
+    )
+}
+
 /// Schedules a recurring timer based on BYOND ticks.
 ///
 /// # Arguments
-/// * `delay` - Number of BYOND ticks to wait before first execution
-/// * `period` - Number of BYOND ticks between recurring executions
+/// * `delay` - Number of BYOND ticks to wait before first execution, or a duration string like `"5m30s"`, see [`Delay`]
+/// * `period` - Number of BYOND ticks between recurring executions, or a duration string
 /// * `owning_obj` - The BYOND object that owns the proc to call
 /// * `proc_path` - The path to the proc to call
 /// * `proc_args` - Arguments to pass to the proc
+/// * `options` - Optional associative list of extra settings, see [`TimerOptions`]
 ///
 /// # Returns
 /// * A UUID string identifying the timer for cancellation
 #[byond_fn]
 pub fn schedule_periodic_tick(
-    delay: u64,
-    period: u64,
+    delay: Delay,
+    period: Delay,
     owning_obj: ByondValue,
     proc_path: ByondValue,
     proc_args: ByondValue,
+    options: TimerOptions,
 ) -> ByondResult<String> {
-    let id = get_uuid(TimerType::ByondTick);
-    let delay = Duration::from_millis(delay);
-    let period = Duration::from_millis(period);
+    let id = options.id(TimerType::ByondTick);
+    let delay = delay.ticks();
+    let period = period.ticks();
+    let options = options.ending_by(u64::from(BYOND_TICK.load(Ordering::Acquire)));
 
     if owning_obj.is_null() || proc_path.is_null() {
         return Err(ByondError::InvalidProc);
@@ -104,11 +204,73 @@ pub fn schedule_periodic_tick(
         return Err(ByondError::InvalidProc);
     };
 
-    schedule_periodic_timer(timers, id, delay, period, owning_obj, proc_path, proc_args);
+    schedule_periodic_timer(
+        timers, id, delay, period, owning_obj, proc_path, proc_args, options,
+    );
 
     Ok(id.to_string())
 }
 
+/// Schedules a debounced one-shot timer based on BYOND ticks.
+///
+/// Every call with the same key pushes the timer back to `delay` from now and replaces its callback,
+/// so the proc only runs once the calls stop for `delay`.
+///
+/// # Arguments
+/// * `key` - Identifies the debounced timer
+/// * `delay` - Number of BYOND ticks without another call before executing the proc, or a duration string like `"5m30s"`, see [`Delay`]
+/// * `owning_obj` - The BYOND object that owns the proc to call
+/// * `proc_path` - The path to the proc to call
+/// * `proc_args` - Arguments to pass to the proc
+/// * `options` - Optional associative list of extra settings, see [`TimerOptions`]
+///
+/// # Returns
+/// * A UUID string identifying the timer for cancellation, the same for every call with the key
+#[byond_fn]
+pub fn schedule_debounced_tick(
+    key: String,
+    delay: Delay,
+    owning_obj: ByondValue,
+    proc_path: ByondValue,
+    proc_args: ByondValue,
+    mut options: TimerOptions,
+) -> ByondResult<String> {
+    options.key = Some(key);
+    options.policy = DuplicatePolicy::Replace;
+
+    schedule_once_tick(delay, owning_obj, proc_path, proc_args, options)
+}
+
+/// Schedules a throttled one-shot timer based on BYOND ticks.
+///
+/// The proc runs at most once per `interval` for each key. A call while the key is cooling down
+/// schedules the proc for the end of the interval, further calls before then only replace its arguments.
+///
+/// # Arguments
+/// * `key` - Identifies the throttled timer
+/// * `interval` - Minimum BYOND ticks between executions of the proc, or a duration string like `"5m30s"`, see [`Delay`]
+/// * `owning_obj` - The BYOND object that owns the proc to call
+/// * `proc_path` - The path to the proc to call
+/// * `proc_args` - Arguments to pass to the proc
+/// * `options` - Optional associative list of extra settings, see [`TimerOptions`]
+///
+/// # Returns
+/// * A UUID string identifying the timer for cancellation, the same for every call with the key
+#[byond_fn]
+pub fn schedule_throttled_tick(
+    key: String,
+    interval: Delay,
+    owning_obj: ByondValue,
+    proc_path: ByondValue,
+    proc_args: ByondValue,
+    mut options: TimerOptions,
+) -> ByondResult<String> {
+    options.key = Some(key);
+    options.policy = DuplicatePolicy::Throttle(interval.ticks());
+
+    schedule_once_tick(Delay::Units(0), owning_obj, proc_path, proc_args, options)
+}
+
 pub fn cancel_timer(id: Uuid) {
     match BYOND_TIMER.lock() {
         Ok(mut timers) => match timers.as_mut() {
@@ -119,15 +281,220 @@ pub fn cancel_timer(id: Uuid) {
     }
 }
 
+pub fn pause_timer(id: Uuid) {
+    match BYOND_TIMER.lock() {
+        Ok(mut timers) => match timers.as_mut() {
+            Some(timers) => timers.pause(&id),
+            None => log_error("BYOND-tick timer thread is unavailable"),
+        },
+        Err(e) => log_error(format!("failed to acquire BYOND-tick timer lock: {e}")),
+    }
+}
+
+pub fn resume_timer(id: Uuid) {
+    match BYOND_TIMER.lock() {
+        Ok(mut timers) => match timers.as_mut() {
+            Some(timers) => timers.resume(&id),
+            None => log_error("BYOND-tick timer thread is unavailable"),
+        },
+        Err(e) => log_error(format!("failed to acquire BYOND-tick timer lock: {e}")),
+    }
+}
+
+pub fn reschedule_timer(id: Uuid, delay: Duration, period: Option<Duration>) {
+    match BYOND_TIMER.lock() {
+        Ok(mut timers) => match timers.as_mut() {
+            Some(timers) => timers.reschedule(&id, delay, period),
+            None => log_error("BYOND-tick timer thread is unavailable"),
+        },
+        Err(e) => log_error(format!("failed to acquire BYOND-tick timer lock: {e}")),
+    }
+}
+
+pub fn snooze_timer(id: Uuid, delta: Duration) {
+    match BYOND_TIMER.lock() {
+        Ok(mut timers) => match timers.as_mut() {
+            Some(timers) => timers.snooze(&id, delta),
+            None => log_error("BYOND-tick timer thread is unavailable"),
+        },
+        Err(e) => log_error(format!("failed to acquire BYOND-tick timer lock: {e}")),
+    }
+}
+
+pub fn timer_info(id: Uuid) -> ByondResult<Option<TimerInfo>> {
+    let mut timers = match BYOND_TIMER.lock() {
+        Ok(timers) => timers,
+        Err(e) => {
+            log_error(format!("failed to acquire BYOND-tick timer lock: {e}"));
+            return Err(ByondError::InvalidProc);
+        }
+    };
+
+    let Some(timers) = timers.as_mut() else {
+        log_error("BYOND-tick timer thread is unavailable");
+        return Err(ByondError::InvalidProc);
+    };
+
+    timers.info(&id).map_err(|e| {
+        log_error(format!(
+            "BYOND-tick timer thread did not answer in time: {e}"
+        ));
+        ByondError::InvalidProc
+    })
+}
+
+pub fn cancel_tag(tag: &str) {
+    match BYOND_TIMER.lock() {
+        Ok(mut timers) => match timers.as_mut() {
+            Some(timers) => timers.cancel_tag(tag),
+            None => log_error("BYOND-tick timer thread is unavailable"),
+        },
+        Err(e) => log_error(format!("failed to acquire BYOND-tick timer lock: {e}")),
+    }
+}
+
+pub fn pause_tag(tag: &str) {
+    match BYOND_TIMER.lock() {
+        Ok(mut timers) => match timers.as_mut() {
+            Some(timers) => timers.pause_tag(tag),
+            None => log_error("BYOND-tick timer thread is unavailable"),
+        },
+        Err(e) => log_error(format!("failed to acquire BYOND-tick timer lock: {e}")),
+    }
+}
+
+pub fn resume_tag(tag: &str) {
+    match BYOND_TIMER.lock() {
+        Ok(mut timers) => match timers.as_mut() {
+            Some(timers) => timers.resume_tag(tag),
+            None => log_error("BYOND-tick timer thread is unavailable"),
+        },
+        Err(e) => log_error(format!("failed to acquire BYOND-tick timer lock: {e}")),
+    }
+}
+
+pub fn list_tag(tag: &str) -> ByondResult<Vec<Uuid>> {
+    let mut timers = match BYOND_TIMER.lock() {
+        Ok(timers) => timers,
+        Err(e) => {
+            log_error(format!("failed to acquire BYOND-tick timer lock: {e}"));
+            return Err(ByondError::InvalidProc);
+        }
+    };
+
+    let Some(timers) = timers.as_mut() else {
+        log_error("BYOND-tick timer thread is unavailable");
+        return Err(ByondError::InvalidProc);
+    };
+
+    timers.list_tag(tag).map_err(|e| {
+        log_error(format!(
+            "BYOND-tick timer thread did not answer in time: {e}"
+        ));
+        ByondError::InvalidProc
+    })
+}
+
+pub fn cancel_owner(owner: u64) {
+    match BYOND_TIMER.lock() {
+        Ok(mut timers) => match timers.as_mut() {
+            Some(timers) => timers.cancel_owner(owner),
+            None => log_error("BYOND-tick timer thread is unavailable"),
+        },
+        Err(e) => log_error(format!("failed to acquire BYOND-tick timer lock: {e}")),
+    }
+}
+
+pub fn list_owner(owner: u64) -> ByondResult<Vec<Uuid>> {
+    let mut timers = match BYOND_TIMER.lock() {
+        Ok(timers) => timers,
+        Err(e) => {
+            log_error(format!("failed to acquire BYOND-tick timer lock: {e}"));
+            return Err(ByondError::InvalidProc);
+        }
+    };
+
+    let Some(timers) = timers.as_mut() else {
+        log_error("BYOND-tick timer thread is unavailable");
+        return Err(ByondError::InvalidProc);
+    };
+
+    timers.list_owner(owner).map_err(|e| {
+        log_error(format!(
+            "BYOND-tick timer thread did not answer in time: {e}"
+        ));
+        ByondError::InvalidProc
+    })
+}
+
 /// Advances the BYOND tick-based timer system by one tick.
 ///
 /// Called by the BYOND runtime to progress the timers that are based on ticks rather than real time.
 /// This function should be called once per BYOND game tick.
+/// In `"main_thread"` dispatch mode it also runs the timers that came due since the last tick,
+/// see [`crate::dispatch::set_dispatch_mode`].
 #[byond_fn]
 pub fn tick_byondtick() {
+    let main_thread = dispatch::dispatch_mode() == DispatchMode::MainThread;
+    match BYOND_TIMER.lock() {
+        Ok(mut timers) => match timers.as_mut() {
+            Some(timers) if main_thread => {
+                // the timers that came due are queued by the time the timer thread answers,
+                // so they run on this very tick
+                if let Err(e) = timers.tick_and_wait() {
+                    log_error(format!(
+                        "BYOND-tick timer thread didn't finish the tick: {e}"
+                    ));
+                }
+                BYOND_TICK.fetch_add(1, Ordering::AcqRel);
+            }
+            Some(timers) => {
+                timers.tick();
+                BYOND_TICK.fetch_add(1, Ordering::AcqRel);
+            }
+            None => log_error("BYOND-tick timer thread is unavailable"),
+        },
+        Err(e) => log_error(format!("failed to acquire BYOND-tick timer lock: {e}")),
+    }
+    if main_thread {
+        dispatch::run_due_timers();
+    }
+}
+
+/// Returns how many ticks the BYOND-tick timers have advanced, the clock of [`schedule_at_tick`].
+///
+/// It starts at 0 when the library is loaded and counts the calls to [`tick_byondtick`], not `world.time`,
+/// so the two drift apart if `world.Tick()` doesn't call it exactly once per tick. Outside of `"main_thread"`
+/// dispatch mode the timer thread works through each tick on its own, so the count can be a tick ahead
+/// of the timers that are firing right now.
+///
+/// # Returns
+/// * The current tick count
+#[byond_fn]
+pub fn get_tick_count() -> u32 {
+    BYOND_TICK.load(Ordering::Acquire)
+}
+
+/// Limits how long each BYOND tick spends calling the procs of tick timers that came due.
+///
+/// Timers that don't fit are deferred to the next tick, where they run by priority and then oldest first, so that thousands of timers
+/// landing on the same tick are spread over several ticks instead of causing a lag spike.
+/// At least one timer runs every tick.
+///
+/// The budget also limits how long each tick spends on the timers queued in `"main_thread"` dispatch mode,
+/// see [`dispatch::run_due_timers`].
+///
+/// # Arguments
+/// * `budget` - Milliseconds per tick, or null (or 0 or less) for no limit
+#[byond_fn]
+pub fn set_tick_budget(budget: Option<f32>) {
+    let budget = budget
+        .filter(|ms| ms.is_finite() && *ms > 0.0)
+        .map(|ms| Duration::from_secs_f32(ms / 1000.0));
+    dispatch::set_tick_budget(budget);
     match BYOND_TIMER.lock() {
         Ok(mut timers) => match timers.as_mut() {
-            Some(timers) => timers.tick(),
+            Some(timers) => timers.set_tick_budget(budget),
             None => log_error("BYOND-tick timer thread is unavailable"),
         },
         Err(e) => log_error(format!("failed to acquire BYOND-tick timer lock: {e}")),
@@ -1,12 +1,41 @@
-use crate::timer::TimerRef;
+use crate::dispatch;
+use crate::duration::{duration_in_ticks, parse_duration};
+use crate::owner::{DroppedTimers, OwnerIdentity, OwnerState};
+use crate::protocol::{CANCEL, DELAY_PREFIX, LEGACY_CANCEL, LEGACY_RESCHEDULE, PAUSE, RESCHEDULE};
+use crate::timer::{
+    AdaptiveClosureState, CatchUpPolicy, ClockJump, ClockJumpPolicy, DuplicatePolicy,
+    LateClosureState, MetaTimer, NextFiring, PeriodMode, TimerInfo, TimerMeta, TimerRef,
+};
+use crate::wallclock::{Deadline, Wakeup};
 use hierarchical_hash_wheel_timer::*;
-use meowtonin::{ByondResult, ByondValue, byond_fn};
-use std::time::Duration;
+use meowtonin::{ByondError, ByondResult, ByondValue, FromByond, byond_fn};
+use std::borrow::Cow;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use uuid::Uuid;
 
-const TIMER_RESCHEDULE: &str = "TIMER_RESCHEDULE";
-const TIMER_CANCEL: &str = "TIMER_CANCEL";
 const ERROR_CALLBACK_PROC: &str = "rt_timer_error";
+const CLOCK_JUMP_PROC: &str = "rt_clock_jumped";
+
+/// Whether timers check that their owner is still alive before calling its proc, see [`set_owner_check`]
+struct OwnerCheck {
+    /// Applies to timers that don't set `check_owner` in their options
+    enabled: bool,
+    /// Var that marks a datum as being deleted when it is true
+    deleted_var: Option<String>,
+    /// Var holding an ID that is unique to each datum, so that a datum reusing a deleted owner's ref isn't taken for it
+    uid_var: Option<String>,
+}
+
+static OWNER_CHECK: Mutex<OwnerCheck> = Mutex::new(OwnerCheck {
+    enabled: false,
+    deleted_var: None,
+    uid_var: None,
+});
+
+/// Number of timers dropped because their owner was gone when they fired
+static DROPPED_TIMERS: DroppedTimers = DroppedTimers::new();
 
 pub enum TimerType {
     RealTime,
@@ -15,9 +44,26 @@ pub enum TimerType {
 
 pub fn get_uuid(utype: TimerType) -> Uuid {
     // this is basically the same as what uuid crate itself does when it creates v4 uuids using the "fast-rng" feature
-    let mut buf: [u8; 16] = rand::random();
+    let buf: [u8; 16] = rand::random();
 
-    // but we mangle one byte, so that we can tell where the id originated in when we cancel. concat_bytes! is nightly :(
+    typed_uuid(utype, buf)
+}
+
+/// Derives the UUID of a keyed timer, so that the same key always maps to the same timer.
+pub fn get_keyed_uuid(utype: TimerType, key: &str) -> Uuid {
+    let mut buf = [0u8; 16];
+    for (half, bytes) in buf.chunks_exact_mut(8).enumerate() {
+        let mut hasher = DefaultHasher::new();
+        half.hash(&mut hasher);
+        key.hash(&mut hasher);
+        bytes.copy_from_slice(&hasher.finish().to_le_bytes());
+    }
+
+    typed_uuid(utype, buf)
+}
+
+fn typed_uuid(utype: TimerType, mut buf: [u8; 16]) -> Uuid {
+    // we mangle one byte, so that we can tell where the id originated in when we cancel. concat_bytes! is nightly :(
     match utype {
         TimerType::RealTime => buf[0] = 0,    // 00
         TimerType::ByondTick => buf[0] = 189, // BD
@@ -25,6 +71,134 @@ pub fn get_uuid(utype: TimerType) -> Uuid {
     Uuid::new_v8(buf)
 }
 
+/// Optional settings for a timer, read from the associative list passed as `options` to the schedule procs.
+///
+/// Keys that are missing from the list keep their default value.
+#[derive(Debug, Default)]
+pub struct TimerOptions {
+    /// `"tag"` - Groups the timer with others so they can be handled together, see [`cancel_tag`]
+    pub tag: Option<String>,
+    /// `"check_owner"` - Overrides the global setting of [`set_owner_check`] for this timer
+    pub check_owner: Option<bool>,
+    /// `"key"` - Makes the timer unique, scheduling another timer with the same key applies the `"policy"` option
+    pub key: Option<String>,
+    /// `"policy"` - `"replace"` (the default), `"keep"` or `"extend"`, see [`DuplicatePolicy`]
+    pub policy: DuplicatePolicy,
+    /// `"max_firings"` - Stops a periodic timer after it called its proc this many times
+    pub max_firings: Option<u32>,
+    /// `"end_after"` - Stops a periodic timer from calling its proc once this many milliseconds
+    /// (or ticks, for BYOND-tick timers) have passed since it was scheduled
+    pub end_after: Option<u64>,
+    /// `"end_at"` - Stops a periodic timer from calling its proc after an absolute time: a UNIX timestamp
+    /// in milliseconds for real-time timers, or a tick count for BYOND-tick timers, see [`ending_by`](Self::ending_by).
+    /// Large timestamps have to be text, see [`UnixMillis`]
+    pub end_at: Option<u64>,
+    /// `"priority"` - Timers that come due on the same tick run highest priority first, the default is 0
+    pub priority: i32,
+    /// `"fixed_rate"` - Whether a periodic timer keeps to its schedule even when its proc is slow,
+    /// see [`PeriodMode`]
+    pub fixed_rate: bool,
+    /// `"catch_up"` - `"fire_all"`, `"fire_once"` or `"skip"`, see [`CatchUpPolicy`].
+    /// Setting it also passes the number of skipped occurrences to the proc as its last argument.
+    pub catch_up: Option<CatchUpPolicy>,
+    /// `"max_lateness"` - One-shot timers only: drops the timer if it fires more than this many milliseconds
+    /// (or ticks, for BYOND-tick timers) past its deadline, e.g. after the server hung
+    pub max_lateness: Option<u64>,
+    /// `"late_proc"` - One-shot timers only: calls this proc of the same owner instead of dropping a timer that
+    /// exceeded its `"max_lateness"`, with the timer's arguments followed by how late it is
+    pub late_proc: Option<String>,
+}
+
+impl TimerOptions {
+    /// The UUID of a new timer, derived from its key if it has one.
+    pub fn id(&self, utype: TimerType) -> Uuid {
+        match &self.key {
+            Some(key) => get_keyed_uuid(utype, key),
+            None => get_uuid(utype),
+        }
+    }
+
+    /// Turns an `"end_at"` time into the time left until it from `now`, on the clock `"end_at"` is in.
+    /// The earlier of `"end_at"` and `"end_after"` applies.
+    pub fn ending_by(mut self, now: u64) -> Self {
+        if let Some(end_at) = self.end_at.take() {
+            let left = end_at.saturating_sub(now);
+            self.end_after = Some(self.end_after.map_or(left, |after| after.min(left)));
+        }
+        self
+    }
+
+    fn meta(&self, owning_obj: &ByondValue) -> TimerMeta {
+        TimerMeta {
+            tag: self.tag.clone(),
+            owner: owner_key(owning_obj),
+            duplicate: self.policy,
+            max_firings: self.max_firings,
+            end_after: self.end_after.map(Duration::from_millis),
+            priority: self.priority,
+            period_mode: if self.fixed_rate {
+                PeriodMode::FixedRate
+            } else {
+                PeriodMode::FixedDelay
+            },
+            catch_up: self.catch_up,
+            max_lateness: self.max_lateness.map(Duration::from_millis),
+        }
+    }
+}
+
+fn parse_duplicate_policy(policy: Option<String>) -> ByondResult<DuplicatePolicy> {
+    match policy.as_deref() {
+        None | Some("replace") => Ok(DuplicatePolicy::Replace),
+        Some("keep") => Ok(DuplicatePolicy::KeepExisting),
+        Some("extend") => Ok(DuplicatePolicy::Extend),
+        Some(other) => Err(ByondError::InvalidConversion {
+            expected: Cow::Borrowed(r#""replace", "keep" or "extend""#),
+            got: Cow::Owned(format!("{other:?}")),
+        }),
+    }
+}
+
+fn parse_catch_up_policy(policy: Option<String>) -> ByondResult<Option<CatchUpPolicy>> {
+    match policy.as_deref() {
+        None => Ok(None),
+        Some("fire_all") => Ok(Some(CatchUpPolicy::FireAll)),
+        Some("fire_once") => Ok(Some(CatchUpPolicy::FireOnce)),
+        Some("skip") => Ok(Some(CatchUpPolicy::Skip)),
+        Some(other) => Err(ByondError::InvalidConversion {
+            expected: Cow::Borrowed(r#""fire_all", "fire_once" or "skip""#),
+            got: Cow::Owned(format!("{other:?}")),
+        }),
+    }
+}
+
+impl FromByond for TimerOptions {
+    fn from_byond(value: ByondValue) -> ByondResult<Self> {
+        if value.is_null() {
+            return Ok(Self::default());
+        }
+
+        Ok(Self {
+            tag: value.read_list_index(&"tag")?,
+            check_owner: value.read_list_index(&"check_owner")?,
+            key: value.read_list_index(&"key")?,
+            policy: parse_duplicate_policy(value.read_list_index(&"policy")?)?,
+            max_firings: value.read_list_index(&"max_firings")?,
+            end_after: value.read_list_index(&"end_after")?,
+            end_at: value
+                .read_list_index::<_, Option<UnixMillis>>(&"end_at")?
+                .map(|end_at| end_at.0),
+            priority: value
+                .read_list_index::<_, Option<i32>>(&"priority")?
+                .unwrap_or_default(),
+            fixed_rate: value.read_list_index(&"fixed_rate")?,
+            catch_up: parse_catch_up_policy(value.read_list_index(&"catch_up")?)?,
+            max_lateness: value.read_list_index(&"max_lateness")?,
+            late_proc: value.read_list_index(&"late_proc")?,
+        })
+    }
+}
+
 pub trait TimerTypable {
     fn timertype(&self) -> TimerType;
 }
@@ -48,6 +222,7 @@ impl TimerTypable for Uuid {
 #[byond_fn]
 pub fn cancel_timer(strid: String) {
     if let Ok(id) = Uuid::parse_str(&strid) {
+        dispatch::forget_due(&id);
         match id.timertype() {
             TimerType::ByondTick => crate::byondtimers::cancel_timer(id),
             TimerType::RealTime => crate::realtimers::cancel_timer(id),
@@ -55,79 +230,753 @@ pub fn cancel_timer(strid: String) {
     }
 }
 
+/// Pauses a scheduled timer, keeping the time it had left until its next firing.
+///
+/// Paused timers do not fire until resumed with [`resume_timer`], and can still be cancelled.
+///
+/// # Arguments
+/// * `strid` - String representation of the timer's UUID
+#[byond_fn]
+pub fn pause_timer(strid: String) {
+    if let Ok(id) = Uuid::parse_str(&strid) {
+        match id.timertype() {
+            TimerType::ByondTick => crate::byondtimers::pause_timer(id),
+            TimerType::RealTime => crate::realtimers::pause_timer(id),
+        }
+    }
+}
+
+/// Resumes a timer paused with [`pause_timer`].
+///
+/// The timer fires after the time it had left when it was paused,
+/// periodic timers then carry on with their usual period.
+///
+/// # Arguments
+/// * `strid` - String representation of the timer's UUID
+#[byond_fn]
+pub fn resume_timer(strid: String) {
+    if let Ok(id) = Uuid::parse_str(&strid) {
+        match id.timertype() {
+            TimerType::ByondTick => crate::byondtimers::resume_timer(id),
+            TimerType::RealTime => crate::realtimers::resume_timer(id),
+        }
+    }
+}
+
+/// Changes when a scheduled timer fires next, keeping its UUID.
+///
+/// Paused timers stay paused, but resume with the new delay.
+///
+/// # Arguments
+/// * `strid` - String representation of the timer's UUID
+/// * `delay` - Milliseconds (or ticks for BYOND-tick timers) from now until the timer fires,
+///   or a duration string like `"5m30s"`, see [`Delay`]
+/// * `period` - New period of a recurring timer in the same form, leave null to keep the old period
+#[byond_fn]
+pub fn reschedule_timer(strid: String, delay: Delay, period: Option<Delay>) {
+    if let Ok(id) = Uuid::parse_str(&strid) {
+        match id.timertype() {
+            TimerType::ByondTick => {
+                crate::byondtimers::reschedule_timer(id, delay.ticks(), period.map(Delay::ticks))
+            }
+            TimerType::RealTime => {
+                crate::realtimers::reschedule_timer(id, delay.millis(), period.map(Delay::millis))
+            }
+        }
+    }
+}
+
+/// Pushes back the next firing of a scheduled timer, keeping its UUID.
+///
+/// # Arguments
+/// * `strid` - String representation of the timer's UUID
+/// * `delta` - Milliseconds (or ticks for BYOND-tick timers) to add to the time left,
+///   or a duration string like `"5m30s"`, see [`Delay`]
+#[byond_fn]
+pub fn snooze_timer(strid: String, delta: Delay) {
+    if let Ok(id) = Uuid::parse_str(&strid) {
+        match id.timertype() {
+            TimerType::ByondTick => crate::byondtimers::snooze_timer(id, delta.ticks()),
+            TimerType::RealTime => crate::realtimers::snooze_timer(id, delta.millis()),
+        }
+    }
+}
+
+/// Looks up the state of a scheduled timer.
+///
+/// Times are in milliseconds for real-time timers and in ticks for BYOND-tick timers.
+///
+/// # Arguments
+/// * `strid` - String representation of the timer's UUID
+///
+/// # Returns
+/// * An associative list with the keys `pending`, `paused`, `remaining`, `periodic` and `period`.
+///   `pending` is false if the timer has already fired, was cancelled or never existed,
+///   paused timers are still pending.
+#[byond_fn]
+pub fn timer_info(strid: String) -> ByondResult<ByondValue> {
+    let info = match Uuid::parse_str(&strid) {
+        Ok(id) => match id.timertype() {
+            TimerType::ByondTick => crate::byondtimers::timer_info(id)?,
+            TimerType::RealTime => crate::realtimers::timer_info(id)?,
+        },
+        Err(_) => None,
+    };
+
+    timer_info_list(info.as_ref())
+}
+
+fn timer_info_list(info: Option<&TimerInfo>) -> ByondResult<ByondValue> {
+    let remaining = info.map_or(0, |i| i.remaining.as_millis());
+    let period = info.and_then(|i| i.period).map_or(0, |p| p.as_millis());
+
+    let mut list = ByondValue::new_list()?;
+    list.write_list_index("pending", info.is_some())?;
+    list.write_list_index("paused", info.is_some_and(|i| i.paused))?;
+    list.write_list_index("remaining", remaining as f32)?;
+    list.write_list_index("periodic", info.is_some_and(|i| i.period.is_some()))?;
+    list.write_list_index("period", period as f32)?;
+    Ok(list)
+}
+
+/// Cancels every scheduled timer with the given tag, in both timer systems.
+///
+/// # Arguments
+/// * `tag` - The tag passed in the `options` of the schedule procs
+#[byond_fn]
+pub fn cancel_tag(tag: String) {
+    crate::realtimers::cancel_tag(&tag);
+    crate::byondtimers::cancel_tag(&tag);
+}
+
+/// Pauses every scheduled timer with the given tag, in both timer systems.
+///
+/// # Arguments
+/// * `tag` - The tag passed in the `options` of the schedule procs
+#[byond_fn]
+pub fn pause_tag(tag: String) {
+    crate::realtimers::pause_tag(&tag);
+    crate::byondtimers::pause_tag(&tag);
+}
+
+/// Resumes every paused timer with the given tag, in both timer systems.
+///
+/// # Arguments
+/// * `tag` - The tag passed in the `options` of the schedule procs
+#[byond_fn]
+pub fn resume_tag(tag: String) {
+    crate::realtimers::resume_tag(&tag);
+    crate::byondtimers::resume_tag(&tag);
+}
+
+/// Lists the scheduled timers with the given tag, in both timer systems.
+///
+/// # Arguments
+/// * `tag` - The tag passed in the `options` of the schedule procs
+///
+/// # Returns
+/// * A list of UUID strings of the pending and paused timers with the tag
+#[byond_fn]
+pub fn list_tag(tag: String) -> ByondResult<Vec<String>> {
+    let mut ids = crate::realtimers::list_tag(&tag)?;
+    ids.extend(crate::byondtimers::list_tag(&tag)?);
+
+    Ok(ids.iter().map(Uuid::to_string).collect())
+}
+
+/// Lists the scheduled timers whose proc is owned by the given datum, in both timer systems.
+///
+/// # Arguments
+/// * `owner` - The datum passed as `owning_obj` to the schedule procs
+///
+/// # Returns
+/// * A list of UUID strings of the pending and paused timers of the datum
+#[byond_fn]
+pub fn timers_for_owner(owner: ByondValue) -> ByondResult<Vec<String>> {
+    let Some(owner) = owner_key(&owner) else {
+        return Ok(Vec::new());
+    };
+    let mut ids = crate::realtimers::list_owner(owner)?;
+    ids.extend(crate::byondtimers::list_owner(owner)?);
+
+    Ok(ids.iter().map(Uuid::to_string).collect())
+}
+
+/// Counts the scheduled timers whose proc is owned by the given datum, in both timer systems.
+///
+/// # Arguments
+/// * `owner` - The datum passed as `owning_obj` to the schedule procs
+#[byond_fn]
+pub fn count_timers_for_owner(owner: ByondValue) -> ByondResult<usize> {
+    let Some(owner) = owner_key(&owner) else {
+        return Ok(0);
+    };
+
+    Ok(crate::realtimers::list_owner(owner)?.len() + crate::byondtimers::list_owner(owner)?.len())
+}
+
+/// Cancels every scheduled timer whose proc is owned by the given datum, in both timer systems.
+///
+/// Meant to be called from `Destroy()`, so that no callbacks fire on deleted datums.
+///
+/// # Arguments
+/// * `owner` - The datum passed as `owning_obj` to the schedule procs
+#[byond_fn]
+pub fn cancel_timers_for_owner(owner: ByondValue) {
+    if let Some(owner) = owner_key(&owner) {
+        crate::realtimers::cancel_owner(owner);
+        crate::byondtimers::cancel_owner(owner);
+    }
+}
+
+/// Configures whether timers owned by a datum are dropped once that datum is gone, rather than calling its proc.
+///
+/// A timer's owner counts as gone once it has been deleted, or while its `deleted_var` is true.
+/// BYOND reuses the refs of deleted datums, so the owner also counts as gone once its ref points to a datum
+/// of another type, or with another `uid_var` than when the timer was scheduled.
+/// Dropped timers are silently skipped, periodic ones are cancelled, see [`dropped_timer_count`].
+/// Timers only remember their owner if the check applies to them when they are scheduled,
+/// so enabling it leaves the timers scheduled before alone.
+///
+/// # Arguments
+/// * `enabled` - Whether to check owners of timers that don't set `check_owner` in their options
+/// * `deleted_var` - Name of a var that is true while a datum is being deleted, or null to only check for deletion
+/// * `uid_var` - Name of a var unique to each datum, read when timers are scheduled, or null to only compare types
+#[byond_fn]
+pub fn set_owner_check(enabled: bool, deleted_var: Option<String>, uid_var: Option<String>) {
+    match OWNER_CHECK.lock() {
+        Ok(mut check) => {
+            check.enabled = enabled;
+            check.deleted_var = deleted_var;
+            check.uid_var = uid_var;
+        }
+        Err(e) => log_error(format!("failed to acquire owner check lock: {e}")),
+    }
+}
+
+/// Returns how many timers have been dropped because their owner was gone, see [`set_owner_check`].
+#[byond_fn]
+pub fn dropped_timer_count() -> usize {
+    DROPPED_TIMERS.count()
+}
+
+/// Identifies a datum in the owner index of the timer threads, `None` for global procs.
+fn owner_key(owning_obj: &ByondValue) -> Option<u64> {
+    if !can_have_procs(owning_obj) {
+        return None;
+    }
+    let ref_id = owning_obj.ref_id()?;
+
+    Some((u64::from(owning_obj.get_type().0) << 32) | u64::from(ref_id))
+}
+
 pub(crate) fn log_error(error: impl Into<String>) {
-    scream_at_byond(error.into());
+    dispatch::dispatch_error(error.into());
 }
 
 pub fn schedule_oneshot_timer(
-    timers: &mut TimerRef<Uuid, OneShotClosureState<Uuid>, PeriodicClosureState<Uuid>>,
+    timers: &mut TimerRef<Uuid, LateClosureState<Uuid>, AdaptiveClosureState<Uuid>>,
     id: Uuid,
     delay: Duration,
     owning_obj: ByondValue,
     proc_path: ByondValue,
     proc_args: ByondValue,
+    options: TimerOptions,
 ) {
-    if can_have_procs(&owning_obj) {
-        // Meowtonin catches panics and converts them to runtimes, but if the closure here panics the timing thread dies and you won't find out
-        // (subsequent calls might panic in the meowtonin thread to let you know tho)
-        timers.schedule_action_once(id, delay, move |_timer_id| {
-            if let Err(e) = call_owned_proc(&owning_obj, &proc_path, &proc_args) {
-                scream_at_byond(e.to_string());
-            }
-        });
-    } else {
-        timers.schedule_action_once(id, delay, move |_timer_id| {
-            if let Err(e) = call_global_proc(&proc_path, &proc_args) {
-                scream_at_byond(e.to_string());
-            }
-        });
-    }
+    let meta = options.meta(&owning_obj);
+    let late_call = late_call(&options, &owning_obj, &proc_args);
+    let call = Arc::new(ProcCall::new(
+        owning_obj,
+        proc_path,
+        proc_args,
+        options.check_owner,
+    ));
+    // Meowtonin catches panics and converts them to runtimes, but if the closure here panics the timing thread dies and you won't find out
+    // (subsequent calls might panic in the meowtonin thread to let you know tho)
+    let state = LateClosureState::new(id, move |timer_id, lateness| {
+        let (call, firing) = match (lateness, &late_call) {
+            (None, _) => (&call, Firing::Once),
+            (Some(lateness), Some(late_call)) => (late_call, Firing::Late(lateness)),
+            // too late, and nothing to tell about it
+            (Some(_), None) => return,
+        };
+        if !dispatch::queue_due(timer_id, call, firing) {
+            call.call_once(firing);
+        }
+    });
+    timers.schedule_once_with_meta(delay, state, meta);
+}
+
+/// The call of the `"late_proc"` of a timer, if it has one.
+fn late_call(
+    options: &TimerOptions,
+    owning_obj: &ByondValue,
+    proc_args: &ByondValue,
+) -> Option<Arc<ProcCall>> {
+    let late_proc = options.late_proc.as_ref()?;
+    Some(Arc::new(ProcCall::new(
+        owning_obj.clone(),
+        ByondValue::new_string(late_proc.as_str()),
+        proc_args.clone(),
+        options.check_owner,
+    )))
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn schedule_periodic_timer(
-    timers: &mut TimerRef<Uuid, OneShotClosureState<Uuid>, PeriodicClosureState<Uuid>>,
+    timers: &mut TimerRef<Uuid, LateClosureState<Uuid>, AdaptiveClosureState<Uuid>>,
     id: Uuid,
     delay: Duration,
     period: Duration,
     owning_obj: ByondValue,
     proc_path: ByondValue,
     proc_args: ByondValue,
+    options: TimerOptions,
 ) {
-    if can_have_procs(&owning_obj) {
-        // Meowtonin catches panics and converts them to runtimes, but if the closure here panics the timing thread dies and you won't find out
-        // (subsequent calls might panic in the meowtonin thread to let you know tho)
-        timers.schedule_action_periodic(id, delay, period, move |_timer_id| match call_owned_proc(
-            &owning_obj,
-            &proc_path,
-            &proc_args,
-        ) {
-            Ok(ret) => should_reschedule(ret),
-            Err(e) => {
+    let meta = options.meta(&owning_obj);
+    let call = Arc::new(
+        ProcCall::new(owning_obj, proc_path, proc_args, options.check_owner)
+            .reporting_skipped(options.catch_up.is_some()),
+    );
+    let state = AdaptiveClosureState::reporting_skipped(id, move |timer_id, skipped| {
+        if dispatch::queue_due(timer_id, &call, Firing::Periodic(skipped)) {
+            // what the proc returns is applied once it has been called
+            return TimerReturn::Reschedule(NextFiring::Period);
+        }
+        call.call_periodic(skipped)
+    });
+    timers.schedule_periodic_with_meta(delay, period, state, meta);
+}
+
+/// Schedules a timer that fires at wall-clock deadlines in UNIX milliseconds, starting with `deadline`.
+///
+/// The wheel only knows relative delays, so the timer keeps checking the system clock as the deadline
+/// approaches, which absorbs adjustments of the system clock. After firing, `next_deadline` picks the next
+/// deadline after the current time, the timer stops once it returns `None` or the proc returns a cancel.
+#[allow(clippy::too_many_arguments)]
+pub fn schedule_wall_clock_timer(
+    timers: &mut TimerRef<Uuid, LateClosureState<Uuid>, AdaptiveClosureState<Uuid>>,
+    id: Uuid,
+    deadline: u64,
+    mut next_deadline: impl FnMut(u64) -> Option<u64> + Send + 'static,
+    owning_obj: ByondValue,
+    proc_path: ByondValue,
+    proc_args: ByondValue,
+    options: TimerOptions,
+) {
+    // the timer wakes up many times per firing and picks each wait itself, so neither limits on its firings
+    // nor how periodic timers keep to their period apply
+    let TimerMeta {
+        tag,
+        owner,
+        duplicate,
+        priority,
+        ..
+    } = options.meta(&owning_obj);
+    let meta = TimerMeta {
+        tag,
+        owner,
+        duplicate,
+        max_firings: None,
+        end_after: None,
+        priority,
+        period_mode: PeriodMode::FixedDelay,
+        catch_up: Some(CatchUpPolicy::FireOnce),
+        max_lateness: None,
+    };
+    let late_call = late_call(&options, &owning_obj, &proc_args);
+    let call = Arc::new(ProcCall::new(
+        owning_obj,
+        proc_path,
+        proc_args,
+        options.check_owner,
+    ));
+    let max_lateness = options.max_lateness;
+    let mut deadline = Deadline(deadline);
+    let state = AdaptiveClosureState::new(id, move |timer_id| {
+        let now = unix_now_ms();
+        let lateness = match deadline.wake(now) {
+            Wakeup::Wait(wait) => return TimerReturn::Reschedule(NextFiring::After(wait)),
+            Wakeup::Due(lateness) => lateness,
+        };
+        let next = next_deadline(now);
+        let ret = if max_lateness.is_none_or(|max| lateness <= max) {
+            let firing = match next {
+                Some(_) => Firing::WallClock,
+                None => Firing::Once,
+            };
+            fire(&call, timer_id, firing)
+        } else if let Some(late_call) = &late_call {
+            fire(
+                late_call,
+                timer_id,
+                Firing::Late(Duration::from_millis(lateness)),
+            )
+        } else {
+            // too late, and nothing to tell about it
+            TimerReturn::Reschedule(NextFiring::Period)
+        };
+        deadline.after_firing(now, ret, next)
+    });
+    let wait = deadline.first_wait(unix_now_ms());
+    timers.schedule_periodic_with_meta(wait, wait, state, meta);
+}
+
+/// Calls the proc of a due timer, or queues it for the BYOND thread, see [`dispatch::queue_due`].
+fn fire(call: &Arc<ProcCall>, id: Uuid, firing: Firing) -> TimerReturn<NextFiring> {
+    if dispatch::queue_due(id, call, firing) {
+        return TimerReturn::Reschedule(NextFiring::Period);
+    }
+    match firing {
+        Firing::Periodic(skipped) => call.call_periodic(skipped),
+        Firing::WallClock => call.call_periodic(0),
+        firing => {
+            call.call_once(firing);
+            TimerReturn::Reschedule(NextFiring::Period)
+        }
+    }
+}
+
+/// Milliseconds since the UNIX epoch according to the system clock.
+pub fn unix_now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_millis() as u64)
+}
+
+/// A UNIX timestamp in milliseconds, read from a number or from text.
+///
+/// DM numbers are only precise to a couple of minutes at current timestamps, so exact times have to be text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnixMillis(pub u64);
+
+impl FromByond for UnixMillis {
+    fn from_byond(value: ByondValue) -> ByondResult<Self> {
+        if !value.is_string() {
+            return Ok(UnixMillis(value.get_number()? as u64));
+        }
+        let text = value.get_string()?;
+        text.trim()
+            .parse()
+            .map(UnixMillis)
+            .map_err(|_| ByondError::InvalidConversion {
+                expected: Cow::Borrowed("a UNIX timestamp in milliseconds"),
+                got: Cow::Owned(format!("{text:?}")),
+            })
+    }
+}
+
+/// A delay or period passed to a schedule proc, as a number in the timer's unit or as a duration string
+///
+/// Strings like `"5m30s"`, `"1.5h"` or `"PT10M"` spare DM the unit conversions, see [`parse_duration`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delay {
+    /// Milliseconds for real-time timers, ticks for BYOND-tick timers
+    Units(u64),
+    /// A duration string, converted to ticks for BYOND-tick timers by the current `world.tick_lag`
+    Duration(Duration),
+}
+
+impl Delay {
+    /// The delay of a real-time timer.
+    pub fn millis(self) -> Duration {
+        match self {
+            Delay::Units(millis) => Duration::from_millis(millis),
+            Delay::Duration(duration) => duration,
+        }
+    }
+
+    /// The delay of a BYOND-tick timer, which counts a tick as a millisecond.
+    pub fn ticks(self) -> Duration {
+        match self {
+            Delay::Units(ticks) => Duration::from_millis(ticks),
+            Delay::Duration(duration) => {
+                // tick_lag is in deciseconds
+                let tick_lag: f32 = ByondValue::world().read_var("tick_lag").unwrap_or(1.0);
+                duration_in_ticks(duration, Duration::from_secs_f32(tick_lag.max(0.0) / 10.0))
+            }
+        }
+    }
+}
+
+impl FromByond for Delay {
+    fn from_byond(value: ByondValue) -> ByondResult<Self> {
+        if !value.is_string() {
+            return u64::from_byond(value).map(Delay::Units);
+        }
+        let text = value.get_string()?;
+        parse_duration(&text)
+            .map(Delay::Duration)
+            .map_err(|e| ByondError::InvalidConversion {
+                expected: Cow::Borrowed(
+                    r#"a number or a duration like "5m30s", "1.5h" or "PT10M""#,
+                ),
+                got: Cow::Owned(format!("{text:?} ({e})")),
+            })
+    }
+}
+
+/// Why a timer calls its proc
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Firing {
+    /// A one-shot timer is due
+    Once,
+    /// A one-shot timer fired this much later than its max lateness allows, see [`TimerMeta::max_lateness`]
+    Late(Duration),
+    /// A periodic timer is due, after skipping this many occurrences, see [`CatchUpPolicy`]
+    Periodic(u32),
+    /// A recurring wall-clock timer reached its deadline, see [`schedule_wall_clock_timer`]
+    WallClock,
+}
+
+/// The proc a timer calls when it fires, and what it calls it with
+pub struct ProcCall {
+    owning_obj: ByondValue,
+    proc_path: ByondValue,
+    proc_args: ByondValue,
+    /// Whether `owning_obj` owns the proc, rather than the proc being global
+    owned: bool,
+    /// What `owning_obj` looked like when the timer was scheduled, `None` for global procs
+    /// and timers the owner check didn't apply to then
+    owner_identity: Option<OwnerIdentity<ByondValue>>,
+    check_owner: Option<bool>,
+    /// Whether the proc of a periodic timer gets the number of skipped occurrences as its last argument
+    report_skipped: bool,
+}
+
+impl ProcCall {
+    pub fn new(
+        owning_obj: ByondValue,
+        proc_path: ByondValue,
+        proc_args: ByondValue,
+        check_owner: Option<bool>,
+    ) -> Self {
+        let owned = can_have_procs(&owning_obj);
+        let owner_identity = owned
+            .then(|| owner_identity(&owning_obj, check_owner))
+            .flatten();
+        ProcCall {
+            owning_obj,
+            proc_path,
+            proc_args,
+            owned,
+            owner_identity,
+            check_owner,
+            report_skipped: false,
+        }
+    }
+
+    /// Sets whether the proc of a periodic timer gets the number of skipped occurrences as its last argument,
+    /// see [`CatchUpPolicy`].
+    pub fn reporting_skipped(mut self, report_skipped: bool) -> Self {
+        self.report_skipped = report_skipped;
+        self
+    }
+
+    /// The arguments to call the proc with when the timer fired.
+    fn args(&self, firing: Firing) -> ByondResult<Vec<ByondValue>> {
+        let mut args = self.proc_args.read_list()?;
+        match firing {
+            Firing::Once => (),
+            Firing::Late(lateness) => args.push(ByondValue::new_num(lateness.as_millis() as f32)),
+            Firing::Periodic(skipped) if self.report_skipped => {
+                args.push(ByondValue::new_num(skipped as f32))
+            }
+            Firing::Periodic(_) | Firing::WallClock => (),
+        }
+        Ok(args)
+    }
+
+    /// Whether the proc's owner is gone, counting the timer as dropped if so, see [`set_owner_check`].
+    pub fn owner_is_gone(&self) -> bool {
+        let gone = self
+            .owner_identity
+            .as_ref()
+            .is_some_and(|identity| owner_is_gone(&self.owning_obj, identity, self.check_owner));
+        DROPPED_TIMERS.count_if(gone)
+    }
+
+    /// Calls the proc, or returns `None` without calling it if its owner is gone.
+    pub fn call(&self, firing: Firing) -> Option<ByondResult<ProcReturn>> {
+        if self.owner_is_gone() {
+            return None;
+        }
+        let args = match self.args(firing) {
+            Ok(args) => args,
+            Err(e) => return Some(Err(e)),
+        };
+        if !self.owned {
+            return Some(call_global_proc(&self.proc_path, args));
+        }
+        Some(call_owned_proc(&self.owning_obj, &self.proc_path, args))
+    }
+
+    /// An associative list describing the call, for DM code that calls the proc itself.
+    ///
+    /// The `"owner"` is null for global procs.
+    pub fn record(&self, id: Uuid, firing: Firing) -> ByondResult<ByondValue> {
+        let owner = if self.owned {
+            self.owning_obj.clone()
+        } else {
+            ByondValue::default()
+        };
+
+        let mut list = ByondValue::new_list()?;
+        list.write_list_index("id", id.to_string())?;
+        list.write_list_index("owner", owner)?;
+        list.write_list_index("proc", self.proc_path.clone())?;
+        list.write_list_index("args", self.args(firing)?)?;
+        let recurring = matches!(firing, Firing::Periodic(_) | Firing::WallClock);
+        list.write_list_index("periodic", recurring)?;
+        list.write_list_index("wall_clock", firing == Firing::WallClock)?;
+        Ok(list)
+    }
+
+    /// Calls the proc of a one-shot timer, which is either on time or late.
+    pub fn call_once(&self, firing: Firing) {
+        if let Some(Err(e)) = self.call(firing) {
+            scream_at_byond(e.to_string());
+        }
+    }
+
+    /// Calls the proc of a periodic timer that skipped `skipped` occurrences since its last call,
+    /// and decides what happens to the timer next.
+    pub fn call_periodic(&self, skipped: u32) -> TimerReturn<NextFiring> {
+        match self.call(Firing::Periodic(skipped)) {
+            Some(Ok(ret)) => should_reschedule(ret),
+            Some(Err(e)) => {
                 scream_at_byond(e.to_string());
                 TimerReturn::Cancel
             }
-        });
-    } else {
-        timers.schedule_action_periodic(
-            id,
-            delay,
-            period,
-            move |_timer_id| match call_global_proc(&proc_path, &proc_args) {
-                Ok(ret) => should_reschedule(ret),
-                Err(e) => {
-                    scream_at_byond(e.to_string());
-                    TimerReturn::Cancel
-                }
-            },
-        );
+            None => TimerReturn::Cancel,
+        }
+    }
+}
+
+/// Reads what a timer needs to recognize its owner when it fires, see [`OwnerIdentity`].
+///
+/// Returns `None` without asking BYOND anything if the owner check doesn't apply to the timer.
+fn owner_identity(
+    owning_obj: &ByondValue,
+    check_owner: Option<bool>,
+) -> Option<OwnerIdentity<ByondValue>> {
+    let uid_var = match OWNER_CHECK.lock() {
+        Ok(check) if check_owner.unwrap_or(check.enabled) => check.uid_var.clone(),
+        Ok(_) => return None,
+        Err(e) => {
+            log_error(format!("failed to acquire owner check lock: {e}"));
+            return None;
+        }
+    };
+    Some(OwnerIdentity {
+        typepath: owning_obj.typepath().unwrap_or_default(),
+        uid: uid_var.and_then(|var| {
+            let uid = owning_obj.read_var(&var).ok()?;
+            Some((var, uid))
+        }),
+    })
+}
+
+/// Whether the owner of a timer has been deleted, is being deleted, or its ref was reused by another datum,
+/// and the owner check applies to the timer.
+fn owner_is_gone(
+    owning_obj: &ByondValue,
+    identity: &OwnerIdentity<ByondValue>,
+    check_owner: Option<bool>,
+) -> bool {
+    let (enabled, deleted_var) = match OWNER_CHECK.lock() {
+        // don't hold the lock while talking to BYOND
+        Ok(check) => (check.enabled, check.deleted_var.clone()),
+        Err(e) => {
+            log_error(format!("failed to acquire owner check lock: {e}"));
+            return false;
+        }
+    };
+    if !check_owner.unwrap_or(enabled) {
+        return false;
+    }
+
+    let current = owning_obj.clone().test_ref().map(|owner| OwnerState {
+        typepath: owner.typepath().unwrap_or_default(),
+        uid: identity
+            .uid
+            .as_ref()
+            .and_then(|(var, _)| owner.read_var(var).ok()),
+        being_deleted: deleted_var.is_some_and(|var| {
+            owner
+                .read_var::<_, ByondValue>(var)
+                .is_ok_and(|deleted| deleted.is_true())
+        }),
+    });
+    identity.is_gone(current.as_ref())
+}
+
+/// What a timer's proc returned, see [`should_reschedule`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum ProcReturn {
+    /// `null`, or anything that is neither text nor a number
+    #[default]
+    Nothing,
+    Text(String),
+    Number(f32),
+}
+
+impl FromByond for ProcReturn {
+    fn from_byond(value: ByondValue) -> ByondResult<Self> {
+        if value.is_string() {
+            Ok(ProcReturn::Text(value.get_string()?))
+        } else if value.is_number() {
+            Ok(ProcReturn::Number(value.get_number()?))
+        } else {
+            Ok(ProcReturn::Nothing)
+        }
+    }
+}
+
+impl From<Option<String>> for ProcReturn {
+    fn from(text: Option<String>) -> Self {
+        text.map_or(ProcReturn::Nothing, ProcReturn::Text)
     }
 }
 
-pub fn should_reschedule(str_in: Option<String>) -> TimerReturn<()> {
-    match str_in.as_deref() {
-        Some(TIMER_RESCHEDULE) => TimerReturn::Reschedule(()),
-        Some(TIMER_CANCEL) => TimerReturn::Cancel,
-        _ => TimerReturn::Reschedule(()),
+impl From<f32> for ProcReturn {
+    fn from(number: f32) -> Self {
+        ProcReturn::Number(number)
+    }
+}
+
+/// Decides what happens to a periodic timer after its proc returned, see [`crate::protocol`].
+///
+/// A new delay until the next call, in milliseconds for real-time timers and in ticks for BYOND-tick timers,
+/// has to be asked for as text, see [`DELAY_PREFIX`]. Numbers other than the return codes keep the timer's period,
+/// so procs that return `TRUE` or a count of something don't change it.
+pub fn should_reschedule(ret: impl Into<ProcReturn>) -> TimerReturn<NextFiring> {
+    let next = match ret.into() {
+        ProcReturn::Text(text) if text == CANCEL.text || text == LEGACY_CANCEL => {
+            return TimerReturn::Cancel;
+        }
+        ProcReturn::Text(text) if text == PAUSE.text => NextFiring::Pause,
+        ProcReturn::Text(text) if text == RESCHEDULE.text || text == LEGACY_RESCHEDULE => {
+            NextFiring::Period
+        }
+        ProcReturn::Text(text) => text
+            .strip_prefix(DELAY_PREFIX)
+            .and_then(|delay| delay.trim().parse().ok())
+            .map_or(NextFiring::Period, next_firing_after),
+        ProcReturn::Number(code) if code == CANCEL.code as f32 => return TimerReturn::Cancel,
+        ProcReturn::Number(code) if code == PAUSE.code as f32 => NextFiring::Pause,
+        ProcReturn::Number(_) => NextFiring::Period,
+        ProcReturn::Nothing => NextFiring::Period,
+    };
+    TimerReturn::Reschedule(next)
+}
+
+/// A returned delay, or the period for anything that isn't a positive number.
+fn next_firing_after(delay: f32) -> NextFiring {
+    if delay.is_finite() && delay > 0.0 {
+        NextFiring::After(Duration::from_millis(delay as u64))
+    } else {
+        NextFiring::Period
     }
 }
 
@@ -137,25 +986,40 @@ pub fn can_have_procs(type_in: &ByondValue) -> bool {
 
 pub fn call_global_proc(
     proc_path_bv: &ByondValue,
-    proc_args_bv: &ByondValue,
-) -> ByondResult<Option<String>> {
+    proc_args: Vec<ByondValue>,
+) -> ByondResult<ProcReturn> {
     let proc_path = proc_path_bv.get_string()?;
-    let proc_args = proc_args_bv.read_list()?;
 
-    meowtonin::call_global::<_, _, _, Option<String>>(proc_path, proc_args)
+    meowtonin::call_global::<_, _, _, ProcReturn>(proc_path, proc_args)
 }
 
 pub fn call_owned_proc(
     proc_owner: &ByondValue,
     proc_path_bv: &ByondValue,
-    proc_args_bv: &ByondValue,
-) -> ByondResult<Option<String>> {
+    proc_args: Vec<ByondValue>,
+) -> ByondResult<ProcReturn> {
     let proc_path = proc_path_bv.get_string()?;
-    let proc_args = proc_args_bv.read_list()?;
 
-    proc_owner.call::<_, _, _, Option<String>>(proc_path, proc_args)
+    proc_owner.call::<_, _, _, ProcReturn>(proc_path, proc_args)
 }
 
 pub fn scream_at_byond(aieee: String) {
     let _ = meowtonin::call_global::<_, _, _, Option<String>>(ERROR_CALLBACK_PROC, [aieee]);
 }
+
+/// Tells DM that real time jumped ahead of a timer thread, see [`crate::dispatch::dispatch_clock_jump`].
+pub fn report_clock_jump(jump: ClockJump) {
+    let policy = match jump.policy {
+        ClockJumpPolicy::FireDue => "fire_due",
+        ClockJumpPolicy::Shift => "shift",
+    };
+    let gap = jump.gap.as_millis() as f32;
+    let args = [
+        ByondValue::new_num(gap),
+        ByondValue::new_string(policy),
+        ByondValue::new_num(jump.fired as f32),
+    ];
+    if let Err(e) = meowtonin::call_global::<_, _, _, Option<String>>(CLOCK_JUMP_PROC, args) {
+        log_error(format!("failed to report clock jump: {e}"));
+    }
+}
@@ -0,0 +1,265 @@
+//! Cron expressions for recurring timers that follow the wall clock, see [`crate::realtimers::schedule_cron`].
+use std::fmt;
+use std::str::FromStr;
+
+/// A standard five-field cron expression: minute, hour, day of month, month and day of week, evaluated in UTC
+///
+/// Fields accept `*`, values, ranges (`1-5`), steps (`*/15`, `0-30/10`, `5/10`) and comma separated lists of those.
+/// Months (`jan`) and days of the week (`mon`) can also be named, and both 0 and 7 are Sunday.
+/// When both the day of month and the day of week are restricted, a day matches if either of them does.
+/// A field that matches every value, like `*/1` or `0-6`, is no more restricted than `*`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CronSchedule {
+    /// Bit `n` is set if the field matches `n`
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    /// Sunday is bit 0
+    weekdays: u64,
+    /// Whether the day of month field matches every day
+    any_day: bool,
+    /// Whether the day of week field matches every day
+    any_weekday: bool,
+}
+
+/// Why a cron expression could not be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CronError {
+    /// The expression does not have exactly five fields
+    FieldCount(usize),
+    /// A field has a value that is out of range or not understood
+    InvalidField { field: &'static str, value: String },
+}
+
+impl fmt::Display for CronError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CronError::FieldCount(count) => write!(
+                f,
+                "expected 5 fields (minute hour day month weekday), got {count}"
+            ),
+            CronError::InvalidField { field, value } => {
+                write!(f, "invalid {field} field {value:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CronError {}
+
+/// The values a cron field accepts
+struct Field {
+    name: &'static str,
+    min: u32,
+    max: u32,
+    /// Names for the values starting at `min`
+    names: &'static [&'static str],
+}
+
+const MINUTE: Field = Field {
+    name: "minute",
+    min: 0,
+    max: 59,
+    names: &[],
+};
+const HOUR: Field = Field {
+    name: "hour",
+    min: 0,
+    max: 23,
+    names: &[],
+};
+const DAY: Field = Field {
+    name: "day of month",
+    min: 1,
+    max: 31,
+    names: &[],
+};
+const MONTH: Field = Field {
+    name: "month",
+    min: 1,
+    max: 12,
+    names: &[
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ],
+};
+const WEEKDAY: Field = Field {
+    name: "day of week",
+    min: 0,
+    max: 7,
+    names: &["sun", "mon", "tue", "wed", "thu", "fri", "sat"],
+};
+
+impl Field {
+    /// Parses the field into a bit set of the values it matches.
+    fn parse(&self, text: &str) -> Result<u64, CronError> {
+        let invalid = || CronError::InvalidField {
+            field: self.name,
+            value: text.to_string(),
+        };
+        let mut bits = 0;
+        for part in text.split(',') {
+            let (range, step) = match part.split_once('/') {
+                Some((range, step)) => {
+                    let step: u32 = step.parse().map_err(|_| invalid())?;
+                    if step == 0 {
+                        return Err(invalid());
+                    }
+                    (range, Some(step))
+                }
+                None => (part, None),
+            };
+            let (first, last) = match range.split_once('-') {
+                _ if range == "*" => (Some(self.min), Some(self.max)),
+                Some((first, last)) => (self.value(first), self.value(last)),
+                // `5/10` runs from 5 to the end of the range
+                None if step.is_some() => (self.value(range), Some(self.max)),
+                None => (self.value(range), self.value(range)),
+            };
+            let (Some(first), Some(last)) = (first, last) else {
+                return Err(invalid());
+            };
+            if first > last {
+                return Err(invalid());
+            }
+            for value in (first..=last).step_by(step.unwrap_or(1) as usize) {
+                bits |= 1 << value;
+            }
+        }
+        Ok(bits)
+    }
+
+    /// The bit set of every value of the field.
+    fn all(&self) -> u64 {
+        (self.min..=self.max).fold(0, |bits, value| bits | 1 << value)
+    }
+
+    /// A single value of the field, as a number or a name.
+    fn value(&self, text: &str) -> Option<u32> {
+        let value = match text.parse() {
+            Ok(value) => value,
+            Err(_) => {
+                let index = self
+                    .names
+                    .iter()
+                    .position(|name| name.eq_ignore_ascii_case(text))?;
+                self.min + index as u32
+            }
+        };
+        (self.min..=self.max).contains(&value).then_some(value)
+    }
+}
+
+impl FromStr for CronSchedule {
+    type Err = CronError;
+
+    fn from_str(expr: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(CronError::FieldCount(fields.len()));
+        };
+        let mut weekday_bits = WEEKDAY.parse(weekdays)?;
+        // 7 is Sunday too
+        if weekday_bits & (1 << 7) != 0 {
+            weekday_bits = (weekday_bits | 1) & !(1 << 7);
+        }
+        let day_bits = DAY.parse(days)?;
+        Ok(CronSchedule {
+            minutes: MINUTE.parse(minutes)?,
+            hours: HOUR.parse(hours)?,
+            days: day_bits,
+            months: MONTH.parse(months)?,
+            weekdays: weekday_bits,
+            any_day: day_bits == DAY.all(),
+            any_weekday: weekday_bits == WEEKDAY.all() & !(1 << 7),
+        })
+    }
+}
+
+const MINUTE_MS: u64 = 60_000;
+const DAY_MINUTES: u64 = 24 * 60;
+/// Expressions that match no time at all, like `0 0 30 2 *`, are given up on after searching this far ahead.
+const SEARCH_MINUTES: u64 = 5 * 366 * DAY_MINUTES;
+
+impl CronSchedule {
+    /// The first time the schedule matches after `after`, both in UNIX milliseconds,
+    /// or `None` if it never matches.
+    pub fn next_after(&self, after: u64) -> Option<u64> {
+        let mut minute = after / MINUTE_MS + 1;
+        let end = minute + SEARCH_MINUTES;
+        while minute < end {
+            let day = minute / DAY_MINUTES;
+            let (year, month, day_of_month) = civil_from_days(day);
+            if self.months & (1 << month) == 0 {
+                let (year, month) = if month == 12 {
+                    (year + 1, 1)
+                } else {
+                    (year, month + 1)
+                };
+                minute = days_from_civil(year, month, 1) * DAY_MINUTES;
+                continue;
+            }
+            if !self.matches_day(day_of_month, weekday(day)) {
+                minute = (day + 1) * DAY_MINUTES;
+                continue;
+            }
+            let hour = minute % DAY_MINUTES / 60;
+            if self.hours & (1 << hour) == 0 {
+                minute = (minute / 60 + 1) * 60;
+                continue;
+            }
+            if self.minutes & (1 << (minute % 60)) == 0 {
+                minute += 1;
+                continue;
+            }
+            return Some(minute * MINUTE_MS);
+        }
+        None
+    }
+
+    fn matches_day(&self, day_of_month: u32, weekday: u32) -> bool {
+        let day = self.days & (1 << day_of_month) != 0;
+        let weekday = self.weekdays & (1 << weekday) != 0;
+        match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+}
+
+/// The day of the week of a day since the UNIX epoch, Sunday is 0.
+fn weekday(days: u64) -> u32 {
+    // 1970-01-01 was a Thursday
+    ((days + 4) % 7) as u32
+}
+
+/// The year, month and day of a day since the UNIX epoch.
+fn civil_from_days(days: u64) -> (u64, u32, u32) {
+    // shifted to start on 0000-03-01, so that leap days end the year
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    } as u32;
+    let year = era * 400 + year_of_era + u64::from(month <= 2);
+    (year, month, day)
+}
+
+/// The day since the UNIX epoch of a date, the inverse of [`civil_from_days`].
+fn days_from_civil(year: u64, month: u32, day: u32) -> u64 {
+    let year = year - u64::from(month <= 2);
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let shifted_month = u64::from((month + 9) % 12);
+    let day_of_year = (153 * shifted_month + 2) / 5 + u64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
@@ -0,0 +1,93 @@
+//! Daily schedules at a local time of day in an IANA time zone, see [`crate::realtimers::schedule_daily`].
+use jiff::Timestamp;
+use jiff::civil::Time;
+use jiff::tz::TimeZone;
+use std::fmt;
+
+/// Every day at a local time of day in a time zone, looked up in the time zone database embedded in the library
+///
+/// Each occurrence is resolved against the zone's rules for that day, so `06:00 Europe/Helsinki` stays at 06:00
+/// local time across daylight saving transitions. A time skipped by a transition fires at the same distance after it,
+/// e.g. 03:30 on a day that jumps from 03:00 to 04:00 fires at 04:30, and a time that happens twice fires the first time.
+#[derive(Debug, Clone)]
+pub struct DailySchedule {
+    time: Time,
+    tz: TimeZone,
+}
+
+/// Why a daily schedule could not be created
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DailyError {
+    /// The time of day is not `HH:MM` or `HH:MM:SS`
+    InvalidTime(String),
+    /// The time zone is not in the time zone database
+    UnknownTimeZone(String),
+}
+
+impl fmt::Display for DailyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DailyError::InvalidTime(time) => {
+                write!(
+                    f,
+                    "invalid time of day {time:?}, expected HH:MM or HH:MM:SS"
+                )
+            }
+            DailyError::UnknownTimeZone(tz) => {
+                write!(
+                    f,
+                    "unknown time zone {tz:?}, expected an IANA name like \"Europe/Helsinki\""
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for DailyError {}
+
+impl DailySchedule {
+    /// A schedule for `time` (`"06:00"`, `"18:30:15"`) every day in the IANA time zone `tz` (`"Europe/Helsinki"`, `"UTC"`).
+    pub fn new(time: &str, tz: &str) -> Result<Self, DailyError> {
+        let parts: Vec<&str> = time.trim().split(':').collect();
+        let (hour, minute, second) = match parts[..] {
+            [hour, minute] => (time_part(hour, 23), time_part(minute, 59), Some(0)),
+            [hour, minute, second] => (
+                time_part(hour, 23),
+                time_part(minute, 59),
+                time_part(second, 59),
+            ),
+            _ => (None, None, None),
+        };
+        let (Some(hour), Some(minute), Some(second)) = (hour, minute, second) else {
+            return Err(DailyError::InvalidTime(time.to_string()));
+        };
+        let tz =
+            TimeZone::get(tz.trim()).map_err(|_| DailyError::UnknownTimeZone(tz.to_string()))?;
+        Ok(DailySchedule {
+            time: Time::constant(hour, minute, second, 0),
+            tz,
+        })
+    }
+
+    /// The first time the schedule matches after `after`, both in UNIX milliseconds,
+    /// or `None` if it is past the range of dates the time zone database handles.
+    pub fn next_after(&self, after: u64) -> Option<u64> {
+        let after = Timestamp::from_millisecond(i64::try_from(after).ok()?).ok()?;
+        let mut date = after.to_zoned(self.tz.clone()).date();
+        loop {
+            let next = date.to_datetime(self.time).to_zoned(self.tz.clone()).ok()?;
+            if next.timestamp() > after {
+                return u64::try_from(next.timestamp().as_millisecond()).ok();
+            }
+            date = date.tomorrow().ok()?;
+        }
+    }
+}
+
+/// One or two digits of a time of day, up to `max`.
+fn time_part(text: &str, max: i8) -> Option<i8> {
+    if text.is_empty() || text.len() > 2 || !text.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    text.parse().ok().filter(|value| *value <= max)
+}
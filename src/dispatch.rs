@@ -0,0 +1,293 @@
+//! Where due timers call their procs: right away on the timer's own thread,
+//! or queued up for the BYOND thread to call them, see [`set_dispatch_mode`].
+use crate::core::*;
+use crate::due::{self, DueQueue, DueTimers};
+use crate::timer::ClockJump;
+use crate::wallclock::wall_clock_return;
+use meowtonin::{ByondError, ByondResult, ByondValue, byond_fn};
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// How due timers call their procs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum DispatchMode {
+    /// The timer thread calls the proc as soon as the timer is due
+    Thread = 0,
+    /// Due timers are queued until [`run_due_timers`] calls their procs on the BYOND thread
+    MainThread = 1,
+    /// Due timers are queued until DM code takes them with [`poll_due`] and calls their procs itself
+    Poll = 2,
+}
+
+static DISPATCH_MODE: AtomicU8 = AtomicU8::new(DispatchMode::Thread as u8);
+
+/// How long [`run_due_timers`] may spend calling procs, see [`crate::byondtimers::set_tick_budget`]
+static TICK_BUDGET: Mutex<Option<Duration>> = Mutex::new(None);
+
+pub fn dispatch_mode() -> DispatchMode {
+    match DISPATCH_MODE.load(Ordering::Relaxed) {
+        1 => DispatchMode::MainThread,
+        2 => DispatchMode::Poll,
+        _ => DispatchMode::Thread,
+    }
+}
+
+fn parse_dispatch_mode(mode: &str) -> ByondResult<DispatchMode> {
+    match mode {
+        "thread" => Ok(DispatchMode::Thread),
+        "main_thread" => Ok(DispatchMode::MainThread),
+        "poll" => Ok(DispatchMode::Poll),
+        other => Err(ByondError::InvalidConversion {
+            expected: Cow::Borrowed(r#""thread", "main_thread" or "poll""#),
+            got: Cow::Owned(format!("{other:?}")),
+        }),
+    }
+}
+
+/// A call into DM waiting for the BYOND thread
+enum DueCall {
+    /// A due timer's proc
+    Timer { call: Arc<ProcCall>, firing: Firing },
+    /// A clock jump to report, queued under the nil id
+    ClockJump(ClockJump),
+    /// An error to report, queued under the nil id
+    Error(String),
+}
+
+static DUE_CALLS: Mutex<DueQueue<Uuid, DueCall>> = Mutex::new(DueQueue::new());
+
+/// Queues a due timer for the BYOND thread, unless the timer thread should call its proc itself.
+///
+/// Returns whether the timer was queued.
+pub fn queue_due(id: Uuid, call: &Arc<ProcCall>, firing: Firing) -> bool {
+    let call = DueCall::Timer {
+        call: Arc::clone(call),
+        firing,
+    };
+    queue(id, call)
+}
+
+/// Reports a clock jump to DM the same way due timers call their procs: right away on the timer thread
+/// that noticed it, or queued for the BYOND thread in between the timers that came due around it.
+///
+/// The timer threads call it for every clock jump, see [`crate::timer::TimerClockJumps`].
+pub fn dispatch_clock_jump(jump: ClockJump) {
+    if !queue(Uuid::nil(), DueCall::ClockJump(jump)) {
+        report_clock_jump(jump);
+    }
+}
+
+/// Reports an error to DM, or queues it for the BYOND thread in the queued dispatch modes,
+/// where the timer threads must never call into DM: the BYOND thread may be waiting on them, see
+/// [`crate::byondtimers::tick_byondtick`].
+///
+/// Errors are reported in the order they happened, along with the queued timers.
+pub fn dispatch_error(error: String) {
+    if dispatch_mode() == DispatchMode::Thread {
+        scream_at_byond(error);
+        return;
+    }
+    // an error about the lock itself has nowhere to go
+    if let Ok(mut due) = DUE_CALLS.lock() {
+        due.push(Uuid::nil(), DueCall::Error(error));
+    }
+}
+
+fn queue(id: Uuid, call: DueCall) -> bool {
+    if dispatch_mode() == DispatchMode::Thread {
+        return false;
+    }
+    let growing = match DUE_CALLS.lock() {
+        Ok(mut due) => due.push(id, call),
+        Err(e) => {
+            log_error(format!("failed to acquire due timer lock: {e}"));
+            None
+        }
+    };
+    if let Some(queued) = growing {
+        log_error(format!(
+            "{queued} due timers are queued for the BYOND thread, is anything running or polling them?"
+        ));
+    }
+    true
+}
+
+/// Drops the queued calls of a cancelled timer.
+///
+/// The timer threads call it for every timer they cancel, see [`crate::timer::TimerCancelling`].
+/// Cancelling a timer by its ID also calls it right away, so that calls run before the timer thread
+/// gets to the cancel don't include it. Timers without queued calls are forgotten without searching the queue.
+pub fn forget_due(id: &Uuid) {
+    match DUE_CALLS.lock() {
+        Ok(mut due) => due.forget(id),
+        Err(e) => log_error(format!("failed to acquire due timer lock: {e}")),
+    }
+}
+
+/// Routes the operations on periodic timers to the timer system each timer belongs to
+struct TimerSystems;
+
+impl DueTimers<Uuid> for TimerSystems {
+    fn cancel(&mut self, id: Uuid) {
+        forget_due(&id);
+        match id.timertype() {
+            TimerType::ByondTick => crate::byondtimers::cancel_timer(id),
+            TimerType::RealTime => crate::realtimers::cancel_timer(id),
+        }
+    }
+
+    fn pause(&mut self, id: Uuid) {
+        match id.timertype() {
+            TimerType::ByondTick => crate::byondtimers::pause_timer(id),
+            TimerType::RealTime => crate::realtimers::pause_timer(id),
+        }
+    }
+
+    fn delay(&mut self, id: Uuid, delay: Duration) {
+        match id.timertype() {
+            TimerType::ByondTick => crate::byondtimers::reschedule_timer(id, delay, None),
+            TimerType::RealTime => crate::realtimers::reschedule_timer(id, delay, None),
+        }
+    }
+}
+
+/// Calls the proc of a queued timer on the BYOND thread.
+fn run_due_call(id: Uuid, due: DueCall) {
+    match due {
+        DueCall::Timer {
+            call,
+            firing: Firing::Periodic(skipped),
+        } => due::apply_periodic_return(&mut TimerSystems, id, call.call_periodic(skipped)),
+        DueCall::Timer {
+            call,
+            firing: Firing::WallClock,
+        } => due::apply_periodic_return(
+            &mut TimerSystems,
+            id,
+            wall_clock_return(call.call_periodic(0)),
+        ),
+        DueCall::Timer { call, firing } => call.call_once(firing),
+        DueCall::ClockJump(jump) => report_clock_jump(jump),
+        DueCall::Error(error) => scream_at_byond(error),
+    }
+}
+
+/// Sets how due timers call their procs.
+///
+/// In `"main_thread"` mode the timer threads never call into DM, they queue due timers instead and
+/// [`run_due_timers`] calls their procs on the BYOND thread, in the order the timers came due.
+/// What the procs of periodic timers return is applied once they have been called.
+///
+/// `"poll"` mode queues due timers the same way, but leaves calling their procs to DM code, see [`poll_due`].
+///
+/// In both queued modes the timer threads don't call into DM at all, errors and clock jumps are queued
+/// along with the timers and reported from the BYOND thread.
+///
+/// Switching back to `"thread"` mode calls the procs of the timers still queued right away,
+/// so that none of them are stranded in the queue.
+///
+/// # Arguments
+/// * `mode` - `"thread"` (the default), `"main_thread"` or `"poll"`
+#[byond_fn]
+pub fn set_dispatch_mode(mode: String) -> ByondResult<()> {
+    let mode = parse_dispatch_mode(&mode)?;
+    DISPATCH_MODE.store(mode as u8, Ordering::Relaxed);
+    if mode == DispatchMode::Thread {
+        // new timers aren't queued anymore, so this runs every timer left
+        due::run_queued(&DUE_CALLS, None, run_due_call);
+    }
+    Ok(())
+}
+
+/// Limits how long each [`run_due_timers`] spends calling procs, like the tick budget of the timer threads.
+pub fn set_tick_budget(budget: Option<Duration>) {
+    match TICK_BUDGET.lock() {
+        Ok(mut tick_budget) => *tick_budget = budget,
+        Err(e) => log_error(format!("failed to acquire tick budget lock: {e}")),
+    }
+}
+
+/// Calls the procs of the timers queued in `"main_thread"` mode, see [`set_dispatch_mode`].
+///
+/// Timers that come due while the procs run are left for the next call, and so are those that don't fit
+/// in the tick budget, see [`crate::byondtimers::set_tick_budget`]. At least one timer runs.
+///
+/// # Returns
+/// * The number of timers that were run
+#[byond_fn]
+pub fn run_due_timers() -> usize {
+    let budget = match TICK_BUDGET.lock() {
+        Ok(budget) => *budget,
+        Err(e) => {
+            log_error(format!("failed to acquire tick budget lock: {e}"));
+            None
+        }
+    };
+    due::run_queued(&DUE_CALLS, budget, run_due_call)
+}
+
+/// Takes the oldest timers queued in `"poll"` mode, for DM code to call their procs itself.
+///
+/// Timers whose owner is gone are dropped instead of returned, see [`set_owner_check`].
+/// Clock jumps and errors queued among the timers are reported as they are taken, see [`dispatch_error`].
+/// Periodic timers keep running at their period, pass what their proc returned to
+/// [`report_timer_return`] to cancel, pause or delay them.
+///
+/// Taken timers are out of the queue for good: one that DM code never calls, e.g. because an earlier proc
+/// runtimed partway through the list, is lost, and a periodic timer whose return is never reported keeps its period.
+///
+/// # Arguments
+/// * `max_count` - Most timers to take, the rest stay queued for the next poll
+///
+/// # Returns
+/// * A list of associative lists with the `"id"`, `"owner"` (null for global procs), `"proc"`,
+///   `"args"`, `"periodic"` and `"wall_clock"` of each timer; the `"args"` of periodic timers with a catch-up
+///   policy end with the number of skipped occurrences
+#[byond_fn]
+pub fn poll_due(max_count: usize) -> ByondResult<ByondValue> {
+    let mut records = ByondValue::new_list()?;
+    due::poll_queued(&DUE_CALLS, max_count, |id, due| -> ByondResult<bool> {
+        let (call, firing) = match due {
+            DueCall::Timer { call, firing } => (call, firing),
+            // poll_due is called on the BYOND thread, so clock jumps are reported right away
+            DueCall::ClockJump(jump) => {
+                report_clock_jump(jump);
+                return Ok(false);
+            }
+            DueCall::Error(error) => {
+                scream_at_byond(error);
+                return Ok(false);
+            }
+        };
+        if call.owner_is_gone() {
+            if matches!(firing, Firing::Periodic(_) | Firing::WallClock) {
+                TimerSystems.cancel(id);
+            }
+            return Ok(false);
+        }
+        records.push_list(call.record(id, firing)?)?;
+        Ok(true)
+    })?;
+    Ok(records)
+}
+
+/// Applies what the proc of a periodic timer returned when DM code called it after [`poll_due`].
+///
+/// # Arguments
+/// * `strid` - String representation of the timer's UUID
+/// * `ret` - What the proc returned, see [`should_reschedule`]
+/// * `wall_clock` - The `"wall_clock"` of the timer's record, whose delays are ignored, see [`wall_clock_return`]
+#[byond_fn]
+pub fn report_timer_return(strid: String, ret: ProcReturn, wall_clock: Option<bool>) {
+    if let Ok(id) = Uuid::parse_str(&strid) {
+        let ret = match wall_clock {
+            Some(true) => wall_clock_return(should_reschedule(ret)),
+            _ => should_reschedule(ret),
+        };
+        due::apply_periodic_return(&mut TimerSystems, id, ret);
+    }
+}
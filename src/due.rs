@@ -0,0 +1,219 @@
+//! The queue of due timers waiting for the BYOND thread to call their procs, see [`crate::dispatch`].
+//!
+//! Kept apart from BYOND so that the order in which queued timers run can be tested on its own.
+use crate::core::log_error;
+use crate::timer::NextFiring;
+use hierarchical_hash_wheel_timer::TimerReturn;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Number of queued timers above which the queue reports that it keeps growing, see [`DueQueue::push`]
+pub const DUE_QUEUE_WARNING: usize = 10_000;
+
+/// Due timers in the order they came due
+///
+/// Forgetting a timer doesn't search the queue for its calls, they are left in place and skipped
+/// when they reach the front.
+#[derive(Debug)]
+pub struct DueQueue<I, T> {
+    /// The calls, each with the number of calls that were queued before it
+    calls: VecDeque<(u64, I, T)>,
+    /// Number of calls still to run for each timer
+    queued: BTreeMap<I, usize>,
+    /// Timers whose calls queued before the first number are skipped, with how many of them are left in `calls`
+    forgotten: BTreeMap<I, (u64, usize)>,
+    /// Number of calls left in `calls` that are skipped
+    skipped: usize,
+    /// Number of calls queued so far
+    pushed: u64,
+    /// Whether the queue has grown past [`DUE_QUEUE_WARNING`] since it was last below half of it
+    warned: bool,
+}
+
+impl<I: Ord + Clone, T> DueQueue<I, T> {
+    pub const fn new() -> Self {
+        DueQueue {
+            calls: VecDeque::new(),
+            queued: BTreeMap::new(),
+            forgotten: BTreeMap::new(),
+            skipped: 0,
+            pushed: 0,
+            warned: false,
+        }
+    }
+
+    /// Queues a due timer.
+    ///
+    /// Returns the length of the queue when it first grows past [`DUE_QUEUE_WARNING`], which means
+    /// nothing is taking timers out of it, e.g. `world/Tick()` isn't calling into rustick in `"main_thread"` mode.
+    pub fn push(&mut self, id: I, call: T) -> Option<usize> {
+        *self.queued.entry(id.clone()).or_default() += 1;
+        self.calls.push_back((self.pushed, id, call));
+        self.pushed += 1;
+        if self.len() < DUE_QUEUE_WARNING / 2 {
+            self.warned = false;
+        }
+        if self.len() > DUE_QUEUE_WARNING && !self.warned {
+            self.warned = true;
+            return Some(self.len());
+        }
+        None
+    }
+
+    pub fn pop_front(&mut self) -> Option<(I, T)> {
+        self.skip_forgotten();
+        let (_, id, call) = self.calls.pop_front()?;
+        if let Some(count) = self.queued.get_mut(&id) {
+            *count -= 1;
+            if *count == 0 {
+                self.queued.remove(&id);
+            }
+        }
+        Some((id, call))
+    }
+
+    /// Takes the oldest call, if it was queued before the queue had seen `mark` calls, see [`DueQueue::mark`].
+    fn pop_front_before(&mut self, mark: u64) -> Option<(I, T)> {
+        self.skip_forgotten();
+        match self.calls.front() {
+            Some((seq, _, _)) if *seq < mark => self.pop_front(),
+            _ => None,
+        }
+    }
+
+    /// Drops the calls of forgotten timers from the front of the queue.
+    fn skip_forgotten(&mut self) {
+        while let Some((seq, id, _)) = self.calls.front() {
+            let Some((before, left)) = self.forgotten.get_mut(id) else {
+                return;
+            };
+            if *seq >= *before {
+                return;
+            }
+            *left -= 1;
+            if *left == 0 {
+                self.forgotten.remove(id);
+            }
+            self.skipped -= 1;
+            self.calls.pop_front();
+        }
+    }
+
+    /// Marks the calls queued so far, to tell them apart from those queued later.
+    fn mark(&self) -> u64 {
+        self.pushed
+    }
+
+    /// Drops the queued calls of a cancelled timer.
+    pub fn forget(&mut self, id: &I) {
+        let Some(count) = self.queued.remove(id) else {
+            return;
+        };
+        let (before, left) = self.forgotten.entry(id.clone()).or_default();
+        *before = self.pushed;
+        *left += count;
+        self.skipped += count;
+    }
+
+    pub fn len(&self) -> usize {
+        self.calls.len() - self.skipped
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<I: Ord + Clone, T> Default for DueQueue<I, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Runs the calls queued before it started with `run`, oldest first, until `budget` is used up.
+///
+/// At least one call runs, the rest stay queued. The lock is only held while taking each call out of the queue,
+/// so `run` may queue, or cancel and forget, other timers. Returns the number of calls that were run.
+pub fn run_queued<I: Ord + Clone, T>(
+    queue: &Mutex<DueQueue<I, T>>,
+    budget: Option<Duration>,
+    mut run: impl FnMut(I, T),
+) -> usize {
+    let start = Instant::now();
+    let mark = match queue.lock() {
+        Ok(queue) => queue.mark(),
+        Err(e) => {
+            log_error(format!("failed to acquire due timer lock: {e}"));
+            return 0;
+        }
+    };
+    let take = || match queue.lock() {
+        Ok(mut queue) => queue.pop_front_before(mark),
+        Err(e) => {
+            log_error(format!("failed to acquire due timer lock: {e}"));
+            None
+        }
+    };
+    let mut ran = 0;
+    while ran == 0 || budget.is_none_or(|budget| start.elapsed() < budget) {
+        let Some((id, call)) = take() else {
+            break;
+        };
+        run(id, call);
+        ran += 1;
+    }
+    ran
+}
+
+/// Takes up to `max_count` calls out of the queue for DM code to run itself, oldest first.
+///
+/// `take` gets each call in turn and returns whether it counts towards `max_count`, e.g. not when the
+/// timer's owner is gone. Taken calls are out of the queue for good, whether or not DM ends up running them.
+/// Returns the number of calls that counted, or the first error from `take`.
+pub fn poll_queued<I: Ord + Clone, T, E>(
+    queue: &Mutex<DueQueue<I, T>>,
+    max_count: usize,
+    mut take: impl FnMut(I, T) -> Result<bool, E>,
+) -> Result<usize, E> {
+    let mut taken = 0;
+    while taken < max_count {
+        let next = match queue.lock() {
+            Ok(mut queue) => queue.pop_front(),
+            Err(e) => {
+                log_error(format!("failed to acquire due timer lock: {e}"));
+                None
+            }
+        };
+        let Some((id, call)) = next else {
+            break;
+        };
+        if take(id, call)? {
+            taken += 1;
+        }
+    }
+    Ok(taken)
+}
+
+/// The timer operations needed to apply what the proc of a queued periodic timer returned
+pub trait DueTimers<I> {
+    fn cancel(&mut self, id: I);
+    fn pause(&mut self, id: I);
+    /// Makes the timer fire after `delay` instead of its period.
+    fn delay(&mut self, id: I, delay: Duration);
+}
+
+/// Applies what the proc of a periodic timer returned, after the timer thread already rescheduled it at its period
+/// when it queued the timer.
+pub fn apply_periodic_return<I>(
+    timers: &mut impl DueTimers<I>,
+    id: I,
+    ret: TimerReturn<NextFiring>,
+) {
+    match ret {
+        TimerReturn::Cancel => timers.cancel(id),
+        TimerReturn::Reschedule(NextFiring::Pause) => timers.pause(id),
+        TimerReturn::Reschedule(NextFiring::After(delay)) => timers.delay(id, delay),
+        TimerReturn::Reschedule(NextFiring::Period) => (),
+    }
+}
@@ -0,0 +1,175 @@
+//! Duration strings like `"5m30s"`, `"1.5h"` or `"PT10M"`, accepted by the schedule procs in place of a number,
+//! see [`crate::core::Delay`].
+use std::fmt;
+use std::time::Duration;
+
+/// The units of the human-readable format, in milliseconds
+const UNITS: [(&str, f64); 5] = [
+    ("ms", 1.0),
+    ("s", 1_000.0),
+    ("m", 60_000.0),
+    ("h", 3_600_000.0),
+    ("d", 86_400_000.0),
+];
+/// The designators of an ISO 8601 duration before the `T`, in the order they have to appear in
+const ISO_DATE_UNITS: [(char, f64); 4] = [
+    ('Y', 0.0),
+    ('M', 0.0),
+    ('W', 7.0 * 86_400_000.0),
+    ('D', 86_400_000.0),
+];
+/// The designators of an ISO 8601 duration after the `T`, in the order they have to appear in
+const ISO_TIME_UNITS: [(char, f64); 3] = [('H', 3_600_000.0), ('M', 60_000.0), ('S', 1_000.0)];
+
+/// Why a duration string could not be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DurationError {
+    /// The string is empty
+    Empty,
+    /// A part of the string doesn't start with a number
+    InvalidNumber(String),
+    /// A number has no unit after it
+    MissingUnit(String),
+    /// A unit of the human-readable format is not one of `ms`, `s`, `m`, `h` or `d`
+    UnknownUnit(String),
+    /// An ISO 8601 duration is malformed
+    InvalidIso(String),
+    /// An ISO 8601 duration uses years or months, which have no fixed length
+    CalendarUnit(String),
+    /// The duration doesn't fit in a millisecond count
+    TooLong,
+}
+
+impl fmt::Display for DurationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DurationError::Empty => write!(f, "empty duration"),
+            DurationError::InvalidNumber(part) => write!(f, "expected a number at {part:?}"),
+            DurationError::MissingUnit(number) => {
+                write!(
+                    f,
+                    "missing unit after {number:?}, expected ms, s, m, h or d"
+                )
+            }
+            DurationError::UnknownUnit(unit) => {
+                write!(f, "unknown unit {unit:?}, expected ms, s, m, h or d")
+            }
+            DurationError::InvalidIso(text) => write!(
+                f,
+                "invalid ISO 8601 duration {text:?}, expected e.g. \"PT10M\" or \"P1DT12H\""
+            ),
+            DurationError::CalendarUnit(text) => write!(
+                f,
+                "ISO 8601 duration {text:?} uses years or months, which have no fixed length"
+            ),
+            DurationError::TooLong => write!(f, "duration is too long"),
+        }
+    }
+}
+
+impl std::error::Error for DurationError {}
+
+/// Parses a duration in the human-readable format (`"5m30s"`, `"1.5h"`, `"2d 12h"`, `"250ms"`)
+/// or as an ISO 8601 duration (`"PT10M"`, `"P1DT12H"`, `"PT0.5S"`), rounded to milliseconds.
+///
+/// A bare number is rejected, as it isn't clear which unit it is in.
+pub fn parse_duration(text: &str) -> Result<Duration, DurationError> {
+    let trimmed = text.trim();
+    if trimmed.is_empty() {
+        return Err(DurationError::Empty);
+    }
+    let millis = match trimmed.strip_prefix(['P', 'p']) {
+        Some(iso) => iso_millis(iso).map_err(|e| match e {
+            DurationError::CalendarUnit(_) => DurationError::CalendarUnit(trimmed.to_string()),
+            _ => DurationError::InvalidIso(trimmed.to_string()),
+        })?,
+        None => components(trimmed)?
+            .into_iter()
+            .map(|(value, unit)| {
+                UNITS
+                    .iter()
+                    .find(|(name, _)| name.eq_ignore_ascii_case(unit))
+                    .map(|(_, scale)| value * scale)
+                    .ok_or_else(|| DurationError::UnknownUnit(unit.to_string()))
+            })
+            .sum::<Result<f64, _>>()?,
+    };
+    if millis > u64::MAX as f64 {
+        return Err(DurationError::TooLong);
+    }
+    Ok(Duration::from_millis(millis.round() as u64))
+}
+
+/// The milliseconds of an ISO 8601 duration after its `P`.
+fn iso_millis(iso: &str) -> Result<f64, DurationError> {
+    let invalid = || DurationError::InvalidIso(iso.to_string());
+    if iso.contains(char::is_whitespace) {
+        return Err(invalid());
+    }
+    let (date, time) = match iso.split_once(['T', 't']) {
+        Some((_, "")) => return Err(invalid()),
+        Some((date, time)) => (date, time),
+        None if iso.is_empty() => return Err(invalid()),
+        None => (iso, ""),
+    };
+    let mut millis = 0.0;
+    for (part, units) in [(date, &ISO_DATE_UNITS[..]), (time, &ISO_TIME_UNITS[..])] {
+        // designators have to be in order, and appear at most once
+        let mut next_unit = 0;
+        for (value, unit) in components(part).map_err(|_| invalid())? {
+            let mut unit = unit.chars();
+            let (Some(designator), None) = (unit.next(), unit.next()) else {
+                return Err(invalid());
+            };
+            let index = units[next_unit..]
+                .iter()
+                .position(|(name, _)| name.eq_ignore_ascii_case(&designator))
+                .ok_or_else(invalid)?;
+            let (_, scale) = units[next_unit + index];
+            if scale == 0.0 {
+                return Err(DurationError::CalendarUnit(iso.to_string()));
+            }
+            millis += value * scale;
+            next_unit += index + 1;
+        }
+    }
+    Ok(millis)
+}
+
+/// Splits `"5m 30s"` into its numbers and their units, which may be separated by whitespace.
+fn components(text: &str) -> Result<Vec<(f64, &str)>, DurationError> {
+    let mut parts = Vec::new();
+    let mut rest = text.trim_start();
+    while !rest.is_empty() {
+        let number_end = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(rest.len());
+        let (number, after) = rest.split_at(number_end);
+        let after = after.trim_start();
+        let unit_end = after
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(after.len());
+        let (unit, after) = after.split_at(unit_end);
+        let Ok(value) = number.parse::<f64>() else {
+            let part = rest.split_whitespace().next().unwrap_or(rest);
+            return Err(DurationError::InvalidNumber(part.to_string()));
+        };
+        if unit.is_empty() {
+            return Err(DurationError::MissingUnit(number.to_string()));
+        }
+        parts.push((value, unit));
+        rest = after.trim_start();
+    }
+    Ok(parts)
+}
+
+/// The number of ticks, of `tick_length` each, that a duration lasts, rounded up so a timer never fires early.
+///
+/// Returned as a [`Duration`] of that many milliseconds, the unit of the BYOND-tick timer wheel.
+pub fn duration_in_ticks(duration: Duration, tick_length: Duration) -> Duration {
+    if tick_length.is_zero() {
+        return duration;
+    }
+    let ticks = duration.as_nanos().div_ceil(tick_length.as_nanos());
+    Duration::from_millis(u64::try_from(ticks).unwrap_or(u64::MAX))
+}
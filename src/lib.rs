@@ -4,8 +4,16 @@ use meowtonin::byond_fn;
 
 pub mod byondtimers;
 pub mod core;
+pub mod cron;
+pub mod daily;
+pub mod dispatch;
+pub mod due;
+pub mod duration;
+pub mod owner;
+pub mod protocol;
 pub mod realtimers;
 pub mod timer;
+pub mod wallclock;
 
 #[cfg(test)]
 mod tests;
@@ -23,3 +31,12 @@ compile_error!(
 pub fn get_version() -> Option<&'static str> {
     Some(env!("CARGO_PKG_VERSION"))
 }
+
+/// Returns the version of the protocol for values returned by recurring timer procs.
+///
+/// # Returns
+/// * [`protocol::PROTOCOL_VERSION`], compare it to `RT_TIMER_PROTOCOL_VERSION` in DM
+#[byond_fn]
+pub fn get_protocol_version() -> u32 {
+    protocol::PROTOCOL_VERSION
+}
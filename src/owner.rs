@@ -0,0 +1,64 @@
+//! Telling whether the datum that owns a timer's proc is gone, see [`crate::core::set_owner_check`].
+//!
+//! BYOND reuses the refs of deleted datums, so a ref that still resolves may point to a different datum
+//! than the one the timer was scheduled on. Timers remember what their owner looked like when they were
+//! scheduled, and compare it with what the ref points to when they fire.
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// What a timer remembers of its owner from when it was scheduled
+#[derive(Debug, Clone, PartialEq)]
+pub struct OwnerIdentity<V> {
+    /// The owner's `type`
+    pub typepath: String,
+    /// The name and value of the owner's unique ID var, if one is configured
+    pub uid: Option<(String, V)>,
+}
+
+/// The datum behind the owner's ref when a timer fires
+#[derive(Debug, Clone, PartialEq)]
+pub struct OwnerState<V> {
+    pub typepath: String,
+    /// The value of the var named in [`OwnerIdentity::uid`], if the timer remembers one
+    pub uid: Option<V>,
+    /// Whether the datum's deleted var is true
+    pub being_deleted: bool,
+}
+
+impl<V: PartialEq> OwnerIdentity<V> {
+    /// Whether the owner is gone, given the datum its ref points to now, `None` if the ref is dead.
+    ///
+    /// A datum of another type, or with another unique ID, took over the ref of a deleted owner.
+    pub fn is_gone(&self, current: Option<&OwnerState<V>>) -> bool {
+        let Some(current) = current else {
+            return true;
+        };
+        current.being_deleted
+            || current.typepath != self.typepath
+            || self
+                .uid
+                .as_ref()
+                .is_some_and(|(_, uid)| current.uid.as_ref() != Some(uid))
+    }
+}
+
+/// Counts the timers dropped because their owner was gone
+#[derive(Debug, Default)]
+pub struct DroppedTimers(AtomicUsize);
+
+impl DroppedTimers {
+    pub const fn new() -> Self {
+        DroppedTimers(AtomicUsize::new(0))
+    }
+
+    /// Counts a timer as dropped if `gone`, and returns `gone`.
+    pub fn count_if(&self, gone: bool) -> bool {
+        if gone {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+        gone
+    }
+
+    pub fn count(&self) -> usize {
+        self.0.load(Ordering::Relaxed)
+    }
+}
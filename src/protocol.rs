@@ -0,0 +1,60 @@
+//! The values a recurring timer's proc returns to decide what happens to the timer next.
+//!
+//! `build.rs` includes this file to generate the matching `#define`s in `rustick.dm`,
+//! so it must not use anything outside of `std`.
+
+/// Version of the return protocol, bumped whenever a return value changes meaning
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// A return value that tells a recurring timer what to do, either as text or as a number
+pub struct ReturnCode {
+    /// Name of the DM `#define` holding the text
+    pub define: &'static str,
+    /// The text a proc returns
+    pub text: &'static str,
+    /// The number a proc returns, negative so that it can't be mistaken for a count or `TRUE` the proc happens to return
+    pub code: i32,
+}
+
+/// Stop the timer for good
+pub const CANCEL: ReturnCode = ReturnCode {
+    define: "RT_TIMER_CANCEL",
+    text: "RT_TIMER_CANCEL",
+    code: -1,
+};
+
+/// Keep calling the proc at the timer's period, the same as returning nothing
+pub const RESCHEDULE: ReturnCode = ReturnCode {
+    define: "RT_TIMER_RESCHEDULE",
+    text: "RT_TIMER_RESCHEDULE",
+    code: -2,
+};
+
+/// Pause the timer until it's resumed, see `rt_resume_timer`
+pub const PAUSE: ReturnCode = ReturnCode {
+    define: "RT_TIMER_PAUSE",
+    text: "RT_TIMER_PAUSE",
+    code: -3,
+};
+
+pub const RETURN_CODES: [ReturnCode; 3] = [CANCEL, RESCHEDULE, PAUSE];
+
+/// Prefix of a new delay, e.g. `"RT_TIMER_DELAY:250"`. Only this text changes the delay,
+/// other numbers a proc returns keep the period, so that procs that happen to return one don't change it.
+pub const DELAY_PREFIX: &str = "RT_TIMER_DELAY:";
+
+/// Texts from before the protocol was versioned, still understood for older DM code
+pub const LEGACY_CANCEL: &str = "TIMER_CANCEL";
+pub const LEGACY_RESCHEDULE: &str = "TIMER_RESCHEDULE";
+
+/// The `#define`s for the return protocol in `rustick.dm`.
+pub fn dm_defines() -> String {
+    let mut defines = format!("#define RT_TIMER_PROTOCOL_VERSION {PROTOCOL_VERSION}\n");
+    for ret in RETURN_CODES {
+        defines += &format!("#define {} \"{}\"\n", ret.define, ret.text);
+        defines += &format!("#define {}_CODE {}\n", ret.define, ret.code);
+    }
+    defines +=
+        &format!("#define RT_TIMER_DELAY(delay) (\"{DELAY_PREFIX}\" + num2text(delay, 12))\n");
+    defines
+}
@@ -1,44 +1,55 @@
 use crate::core::*;
+use crate::cron::CronSchedule;
+use crate::daily::DailySchedule;
 use crate::timer::*;
 use hierarchical_hash_wheel_timer::*;
 use meowtonin::{ByondError, ByondResult, ByondValue, byond_fn};
+use std::borrow::Cow;
 use std::sync::{LazyLock, Mutex};
 use std::time::Duration;
 use uuid::Uuid;
 
-type TimerCoreType = TimerWithThread<Uuid, OneShotClosureState<Uuid>, PeriodicClosureState<Uuid>>;
-type TimerRefType = TimerRef<Uuid, OneShotClosureState<Uuid>, PeriodicClosureState<Uuid>>;
+type TimerCoreType = TimerWithThread<Uuid, LateClosureState<Uuid>, AdaptiveClosureState<Uuid>>;
+type TimerRefType = TimerRef<Uuid, LateClosureState<Uuid>, AdaptiveClosureState<Uuid>>;
 
 pub static TIMER_CORE: LazyLock<Option<TimerCoreType>> =
-    LazyLock::new(|| match TimerWithThread::for_uuid_closures() {
+    LazyLock::new(|| match TimerWithThread::for_uuid_adaptive_closures() {
         Ok(timer) => Some(timer),
         Err(e) => {
             log_error(format!("failed to start real-time timer thread: {e}"));
             None
         }
     });
-pub static TIMER: LazyLock<Mutex<Option<TimerRefType>>> =
-    LazyLock::new(|| Mutex::new(TIMER_CORE.as_ref().map(TimerWithThread::timer_ref)));
+pub static TIMER: LazyLock<Mutex<Option<TimerRefType>>> = LazyLock::new(|| {
+    Mutex::new(TIMER_CORE.as_ref().map(|core| {
+        let mut timers = core.timer_ref();
+        timers.on_clock_jump(crate::dispatch::dispatch_clock_jump);
+        timers.on_cancel(crate::dispatch::forget_due);
+        timers
+    }))
+});
 
 /// Schedules a one-shot timer based on real-time (milliseconds).
 ///
 /// # Arguments
-/// * `delay` - Milliseconds to wait before executing the proc
+/// * `delay` - Milliseconds to wait before executing the proc, or a duration string like `"5m30s"`, see [`Delay`]
 /// * `owning_obj` - The BYOND object that owns the proc to call
 /// * `proc_path` - The path to the proc to call
 /// * `proc_args` - Arguments to pass to the proc
+/// * `options` - Optional associative list of extra settings, see [`TimerOptions`]
 ///
 /// # Returns
 /// * A UUID string identifying the timer for cancellation
 #[byond_fn]
 pub fn schedule_once(
-    delay: u64,
+    delay: Delay,
     owning_obj: ByondValue,
     proc_path: ByondValue,
     proc_args: ByondValue,
+    options: TimerOptions,
 ) -> ByondResult<String> {
-    let id = get_uuid(TimerType::RealTime);
-    let delay = Duration::from_millis(delay);
+    let id = options.id(TimerType::RealTime);
+    let delay = delay.millis();
 
     if owning_obj.is_null() || proc_path.is_null() {
         return Err(ByondError::InvalidProc);
@@ -57,7 +68,197 @@ pub fn schedule_once(
         return Err(ByondError::InvalidProc);
     };
 
-    schedule_oneshot_timer(timers, id, delay, owning_obj, proc_path, proc_args);
+    schedule_oneshot_timer(timers, id, delay, owning_obj, proc_path, proc_args, options);
+
+    Ok(id.to_string())
+}
+
+/// Schedules a one-shot timer for an absolute wall-clock time.
+///
+/// The timer keeps checking the system clock as the time approaches, so that adjustments of the
+/// system clock (e.g. by NTP) don't make it fire early or late. Times in the past fire right away.
+///
+/// # Arguments
+/// * `ts_ms` - UNIX timestamp in milliseconds, as text to be exact, see [`UnixMillis`]
+/// * `owning_obj` - The BYOND object that owns the proc to call
+/// * `proc_path` - The path to the proc to call
+/// * `proc_args` - Arguments to pass to the proc
+/// * `options` - Optional associative list of extra settings, see [`TimerOptions`]
+///
+/// # Returns
+/// * A UUID string identifying the timer for cancellation
+#[byond_fn]
+pub fn schedule_at_unix(
+    ts_ms: UnixMillis,
+    owning_obj: ByondValue,
+    proc_path: ByondValue,
+    proc_args: ByondValue,
+    options: TimerOptions,
+) -> ByondResult<String> {
+    let id = options.id(TimerType::RealTime);
+
+    if owning_obj.is_null() || proc_path.is_null() {
+        return Err(ByondError::InvalidProc);
+    }
+
+    let mut timers = match TIMER.lock() {
+        Ok(timers) => timers,
+        Err(e) => {
+            log_error(format!("failed to acquire real-time timer lock: {e}"));
+            return Err(ByondError::InvalidProc);
+        }
+    };
+
+    let Some(timers) = timers.as_mut() else {
+        log_error("real-time timer thread is unavailable");
+        return Err(ByondError::InvalidProc);
+    };
+
+    schedule_wall_clock_timer(
+        timers,
+        id,
+        ts_ms.0,
+        |_| None,
+        owning_obj,
+        proc_path,
+        proc_args,
+        options,
+    );
+
+    Ok(id.to_string())
+}
+
+/// Schedules a recurring timer that fires whenever a cron expression matches the wall clock, in UTC.
+///
+/// Each occurrence is computed from the expression, so the timer doesn't drift off the wall clock over
+/// long uptimes. The proc can return a cancel to stop the timer, see [`crate::protocol`].
+///
+/// # Arguments
+/// * `expr` - Five-field cron expression, e.g. `"0 */6 * * *"` for every six hours, see [`CronSchedule`]
+/// * `owning_obj` - The BYOND object that owns the proc to call
+/// * `proc_path` - The path to the proc to call
+/// * `proc_args` - Arguments to pass to the proc
+/// * `options` - Optional associative list of extra settings, see [`TimerOptions`]
+///
+/// # Returns
+/// * A UUID string identifying the timer for cancellation
+#[byond_fn]
+pub fn schedule_cron(
+    expr: String,
+    owning_obj: ByondValue,
+    proc_path: ByondValue,
+    proc_args: ByondValue,
+    options: TimerOptions,
+) -> ByondResult<String> {
+    let id = options.id(TimerType::RealTime);
+    let schedule: CronSchedule = expr.parse().map_err(|e| ByondError::InvalidConversion {
+        expected: Cow::Borrowed(r#"a cron expression like "0 */6 * * *""#),
+        got: Cow::Owned(format!("{expr:?} ({e})")),
+    })?;
+    let Some(first) = schedule.next_after(unix_now_ms()) else {
+        return Err(ByondError::InvalidConversion {
+            expected: Cow::Borrowed("a cron expression that matches some time"),
+            got: Cow::Owned(format!("{expr:?}")),
+        });
+    };
+
+    if owning_obj.is_null() || proc_path.is_null() {
+        return Err(ByondError::InvalidProc);
+    }
+
+    let mut timers = match TIMER.lock() {
+        Ok(timers) => timers,
+        Err(e) => {
+            log_error(format!("failed to acquire real-time timer lock: {e}"));
+            return Err(ByondError::InvalidProc);
+        }
+    };
+
+    let Some(timers) = timers.as_mut() else {
+        log_error("real-time timer thread is unavailable");
+        return Err(ByondError::InvalidProc);
+    };
+
+    schedule_wall_clock_timer(
+        timers,
+        id,
+        first,
+        move |now| schedule.next_after(now),
+        owning_obj,
+        proc_path,
+        proc_args,
+        options,
+    );
+
+    Ok(id.to_string())
+}
+
+/// Schedules a recurring timer that fires every day at a local time of day in an IANA time zone.
+///
+/// Occurrences follow the zone's daylight saving rules from the time zone database embedded in the library,
+/// so `"06:00"` in `"Europe/Helsinki"` stays at 06:00 local time all year, see [`DailySchedule`].
+/// The proc can return a cancel to stop the timer, see [`crate::protocol`].
+///
+/// # Arguments
+/// * `time` - Local time of day as `"HH:MM"` or `"HH:MM:SS"`
+/// * `tz` - IANA time zone name, e.g. `"Europe/Helsinki"` or `"UTC"`
+/// * `owning_obj` - The BYOND object that owns the proc to call
+/// * `proc_path` - The path to the proc to call
+/// * `proc_args` - Arguments to pass to the proc
+/// * `options` - Optional associative list of extra settings, see [`TimerOptions`]
+///
+/// # Returns
+/// * A UUID string identifying the timer for cancellation
+#[byond_fn]
+pub fn schedule_daily(
+    time: String,
+    tz: String,
+    owning_obj: ByondValue,
+    proc_path: ByondValue,
+    proc_args: ByondValue,
+    options: TimerOptions,
+) -> ByondResult<String> {
+    let id = options.id(TimerType::RealTime);
+    let schedule = DailySchedule::new(&time, &tz).map_err(|e| ByondError::InvalidConversion {
+        expected: Cow::Borrowed(
+            r#"a time of day like "06:00" and a time zone like "Europe/Helsinki""#,
+        ),
+        got: Cow::Owned(e.to_string()),
+    })?;
+    let Some(first) = schedule.next_after(unix_now_ms()) else {
+        return Err(ByondError::InvalidConversion {
+            expected: Cow::Borrowed("a daily time that occurs again"),
+            got: Cow::Owned(format!("{time:?} in {tz:?}")),
+        });
+    };
+
+    if owning_obj.is_null() || proc_path.is_null() {
+        return Err(ByondError::InvalidProc);
+    }
+
+    let mut timers = match TIMER.lock() {
+        Ok(timers) => timers,
+        Err(e) => {
+            log_error(format!("failed to acquire real-time timer lock: {e}"));
+            return Err(ByondError::InvalidProc);
+        }
+    };
+
+    let Some(timers) = timers.as_mut() else {
+        log_error("real-time timer thread is unavailable");
+        return Err(ByondError::InvalidProc);
+    };
+
+    schedule_wall_clock_timer(
+        timers,
+        id,
+        first,
+        move |now| schedule.next_after(now),
+        owning_obj,
+        proc_path,
+        proc_args,
+        options,
+    );
 
     Ok(id.to_string())
 }
@@ -65,25 +266,28 @@ pub fn schedule_once(
 /// Schedules a recurring timer based on real-time (milliseconds).
 ///
 /// # Arguments
-/// * `delay` - Milliseconds to wait before first execution
-/// * `period` - Milliseconds between recurring executions
+/// * `delay` - Milliseconds to wait before first execution, or a duration string like `"5m30s"`, see [`Delay`]
+/// * `period` - Milliseconds between recurring executions, or a duration string
 /// * `owning_obj` - The BYOND object that owns the proc to call
 /// * `proc_path` - The path to the proc to call
 /// * `proc_args` - Arguments to pass to the proc
+/// * `options` - Optional associative list of extra settings, see [`TimerOptions`]
 ///
 /// # Returns
 /// * A UUID string identifying the timer for cancellation
 #[byond_fn]
 pub fn schedule_periodic(
-    delay: u64,
-    period: u64,
+    delay: Delay,
+    period: Delay,
     owning_obj: ByondValue,
     proc_path: ByondValue,
     proc_args: ByondValue,
+    options: TimerOptions,
 ) -> ByondResult<String> {
-    let id = get_uuid(TimerType::RealTime);
-    let delay = Duration::from_millis(delay);
-    let period = Duration::from_millis(period);
+    let id = options.id(TimerType::RealTime);
+    let delay = delay.millis();
+    let period = period.millis();
+    let options = options.ending_by(unix_now_ms());
 
     if owning_obj.is_null() || proc_path.is_null() {
         return Err(ByondError::InvalidProc);
@@ -102,11 +306,105 @@ pub fn schedule_periodic(
         return Err(ByondError::InvalidProc);
     };
 
-    schedule_periodic_timer(timers, id, delay, period, owning_obj, proc_path, proc_args);
+    schedule_periodic_timer(
+        timers, id, delay, period, owning_obj, proc_path, proc_args, options,
+    );
 
     Ok(id.to_string())
 }
 
+/// Schedules a debounced one-shot timer based on real-time (milliseconds).
+///
+/// Every call with the same key pushes the timer back to `delay` from now and replaces its callback,
+/// so the proc only runs once the calls stop for `delay`.
+///
+/// # Arguments
+/// * `key` - Identifies the debounced timer
+/// * `delay` - Milliseconds without another call before executing the proc, or a duration string like `"5m30s"`, see [`Delay`]
+/// * `owning_obj` - The BYOND object that owns the proc to call
+/// * `proc_path` - The path to the proc to call
+/// * `proc_args` - Arguments to pass to the proc
+/// * `options` - Optional associative list of extra settings, see [`TimerOptions`]
+///
+/// # Returns
+/// * A UUID string identifying the timer for cancellation, the same for every call with the key
+#[byond_fn]
+pub fn schedule_debounced(
+    key: String,
+    delay: Delay,
+    owning_obj: ByondValue,
+    proc_path: ByondValue,
+    proc_args: ByondValue,
+    mut options: TimerOptions,
+) -> ByondResult<String> {
+    options.key = Some(key);
+    options.policy = DuplicatePolicy::Replace;
+
+    schedule_once(delay, owning_obj, proc_path, proc_args, options)
+}
+
+/// Schedules a throttled one-shot timer based on real-time (milliseconds).
+///
+/// The proc runs at most once per `interval` for each key. A call while the key is cooling down
+/// schedules the proc for the end of the interval, further calls before then only replace its arguments.
+///
+/// # Arguments
+/// * `key` - Identifies the throttled timer
+/// * `interval` - Minimum milliseconds between executions of the proc, or a duration string like `"5m30s"`, see [`Delay`]
+/// * `owning_obj` - The BYOND object that owns the proc to call
+/// * `proc_path` - The path to the proc to call
+/// * `proc_args` - Arguments to pass to the proc
+/// * `options` - Optional associative list of extra settings, see [`TimerOptions`]
+///
+/// # Returns
+/// * A UUID string identifying the timer for cancellation, the same for every call with the key
+#[byond_fn]
+pub fn schedule_throttled(
+    key: String,
+    interval: Delay,
+    owning_obj: ByondValue,
+    proc_path: ByondValue,
+    proc_args: ByondValue,
+    mut options: TimerOptions,
+) -> ByondResult<String> {
+    options.key = Some(key);
+    options.policy = DuplicatePolicy::Throttle(interval.millis());
+
+    schedule_once(Delay::Units(0), owning_obj, proc_path, proc_args, options)
+}
+
+/// Sets what the real-time timer thread does when real time jumps ahead of it, e.g. after the
+/// server was suspended, stopped in a debugger or hung for a while.
+///
+/// Every jump is reported to DM, see [`crate::dispatch::dispatch_clock_jump`].
+///
+/// # Arguments
+/// * `policy` - `"fire_due"` (the default) fires every timer that came due during the jump,
+///   `"shift"` pushes every timer back by the jump, as if it had not happened
+/// * `threshold` - Milliseconds real time has to jump ahead at once to count as a jump, 1000 if null
+#[byond_fn]
+pub fn set_clock_jump_policy(policy: String, threshold: Option<u64>) -> ByondResult<()> {
+    let policy = match policy.as_str() {
+        "fire_due" => ClockJumpPolicy::FireDue,
+        "shift" => ClockJumpPolicy::Shift,
+        other => {
+            return Err(ByondError::InvalidConversion {
+                expected: Cow::Borrowed(r#""fire_due" or "shift""#),
+                got: Cow::Owned(format!("{other:?}")),
+            });
+        }
+    };
+    let threshold = Duration::from_millis(threshold.unwrap_or(1_000));
+    match TIMER.lock() {
+        Ok(mut timers) => match timers.as_mut() {
+            Some(timers) => timers.set_clock_jump_policy(policy, threshold),
+            None => log_error("real-time timer thread is unavailable"),
+        },
+        Err(e) => log_error(format!("failed to acquire real-time timer lock: {e}")),
+    }
+    Ok(())
+}
+
 /// Cancels a real-time timer based on its UUID.
 pub fn cancel_timer(id: Uuid) {
     match TIMER.lock() {
@@ -117,3 +415,160 @@ pub fn cancel_timer(id: Uuid) {
         Err(e) => log_error(format!("failed to acquire real-time timer lock: {e}")),
     }
 }
+
+/// Pauses a real-time timer based on its UUID.
+pub fn pause_timer(id: Uuid) {
+    match TIMER.lock() {
+        Ok(mut timers) => match timers.as_mut() {
+            Some(timers) => timers.pause(&id),
+            None => log_error("real-time timer thread is unavailable"),
+        },
+        Err(e) => log_error(format!("failed to acquire real-time timer lock: {e}")),
+    }
+}
+
+/// Resumes a paused real-time timer based on its UUID.
+pub fn resume_timer(id: Uuid) {
+    match TIMER.lock() {
+        Ok(mut timers) => match timers.as_mut() {
+            Some(timers) => timers.resume(&id),
+            None => log_error("real-time timer thread is unavailable"),
+        },
+        Err(e) => log_error(format!("failed to acquire real-time timer lock: {e}")),
+    }
+}
+
+/// Moves a real-time timer based on its UUID, keeping the UUID.
+pub fn reschedule_timer(id: Uuid, delay: Duration, period: Option<Duration>) {
+    match TIMER.lock() {
+        Ok(mut timers) => match timers.as_mut() {
+            Some(timers) => timers.reschedule(&id, delay, period),
+            None => log_error("real-time timer thread is unavailable"),
+        },
+        Err(e) => log_error(format!("failed to acquire real-time timer lock: {e}")),
+    }
+}
+
+/// Pushes back a real-time timer based on its UUID.
+pub fn snooze_timer(id: Uuid, delta: Duration) {
+    match TIMER.lock() {
+        Ok(mut timers) => match timers.as_mut() {
+            Some(timers) => timers.snooze(&id, delta),
+            None => log_error("real-time timer thread is unavailable"),
+        },
+        Err(e) => log_error(format!("failed to acquire real-time timer lock: {e}")),
+    }
+}
+
+/// Looks up the state of a real-time timer based on its UUID.
+pub fn timer_info(id: Uuid) -> ByondResult<Option<TimerInfo>> {
+    let mut timers = match TIMER.lock() {
+        Ok(timers) => timers,
+        Err(e) => {
+            log_error(format!("failed to acquire real-time timer lock: {e}"));
+            return Err(ByondError::InvalidProc);
+        }
+    };
+
+    let Some(timers) = timers.as_mut() else {
+        log_error("real-time timer thread is unavailable");
+        return Err(ByondError::InvalidProc);
+    };
+
+    timers.info(&id).map_err(|e| {
+        log_error(format!(
+            "real-time timer thread did not answer in time: {e}"
+        ));
+        ByondError::InvalidProc
+    })
+}
+
+/// Cancels all real-time timers with the given tag.
+pub fn cancel_tag(tag: &str) {
+    match TIMER.lock() {
+        Ok(mut timers) => match timers.as_mut() {
+            Some(timers) => timers.cancel_tag(tag),
+            None => log_error("real-time timer thread is unavailable"),
+        },
+        Err(e) => log_error(format!("failed to acquire real-time timer lock: {e}")),
+    }
+}
+
+/// Pauses all real-time timers with the given tag.
+pub fn pause_tag(tag: &str) {
+    match TIMER.lock() {
+        Ok(mut timers) => match timers.as_mut() {
+            Some(timers) => timers.pause_tag(tag),
+            None => log_error("real-time timer thread is unavailable"),
+        },
+        Err(e) => log_error(format!("failed to acquire real-time timer lock: {e}")),
+    }
+}
+
+/// Resumes all real-time timers with the given tag.
+pub fn resume_tag(tag: &str) {
+    match TIMER.lock() {
+        Ok(mut timers) => match timers.as_mut() {
+            Some(timers) => timers.resume_tag(tag),
+            None => log_error("real-time timer thread is unavailable"),
+        },
+        Err(e) => log_error(format!("failed to acquire real-time timer lock: {e}")),
+    }
+}
+
+/// Lists the real-time timers with the given tag.
+pub fn list_tag(tag: &str) -> ByondResult<Vec<Uuid>> {
+    let mut timers = match TIMER.lock() {
+        Ok(timers) => timers,
+        Err(e) => {
+            log_error(format!("failed to acquire real-time timer lock: {e}"));
+            return Err(ByondError::InvalidProc);
+        }
+    };
+
+    let Some(timers) = timers.as_mut() else {
+        log_error("real-time timer thread is unavailable");
+        return Err(ByondError::InvalidProc);
+    };
+
+    timers.list_tag(tag).map_err(|e| {
+        log_error(format!(
+            "real-time timer thread did not answer in time: {e}"
+        ));
+        ByondError::InvalidProc
+    })
+}
+
+/// Cancels all real-time timers belonging to the owner.
+pub fn cancel_owner(owner: u64) {
+    match TIMER.lock() {
+        Ok(mut timers) => match timers.as_mut() {
+            Some(timers) => timers.cancel_owner(owner),
+            None => log_error("real-time timer thread is unavailable"),
+        },
+        Err(e) => log_error(format!("failed to acquire real-time timer lock: {e}")),
+    }
+}
+
+/// Lists the real-time timers belonging to the owner.
+pub fn list_owner(owner: u64) -> ByondResult<Vec<Uuid>> {
+    let mut timers = match TIMER.lock() {
+        Ok(timers) => timers,
+        Err(e) => {
+            log_error(format!("failed to acquire real-time timer lock: {e}"));
+            return Err(ByondError::InvalidProc);
+        }
+    };
+
+    let Some(timers) = timers.as_mut() else {
+        log_error("real-time timer thread is unavailable");
+        return Err(ByondError::InvalidProc);
+    };
+
+    timers.list_owner(owner).map_err(|e| {
+        log_error(format!(
+            "real-time timer thread did not answer in time: {e}"
+        ));
+        ByondError::InvalidProc
+    })
+}
@@ -1,12 +1,59 @@
-use crate::core::{TimerTypable, TimerType, get_uuid, should_reschedule};
-use hierarchical_hash_wheel_timer::TimerReturn;
+use crate::byondtimers::{ticks_until, world_time_tick};
+use crate::core::{TimerTypable, TimerType, get_keyed_uuid, get_uuid, should_reschedule};
+use crate::cron::{CronError, CronSchedule};
+use crate::daily::{DailyError, DailySchedule};
+use crate::due::{
+    DUE_QUEUE_WARNING, DueQueue, DueTimers, apply_periodic_return, poll_queued, run_queued,
+};
+use crate::duration::{DurationError, duration_in_ticks, parse_duration};
+use crate::owner::{DroppedTimers, OwnerIdentity, OwnerState};
+use crate::protocol::{CANCEL, PAUSE};
+use crate::timer::{
+    AdaptiveClosureState, CatchUpPolicy, ClockJump, ClockJumpPolicy, DuplicatePolicy,
+    LateClosureState, ManualClock, MetaTimer, NextFiring, PeriodMode, TimeSource, TimerCancelling,
+    TimerClockJumps, TimerInfo, TimerMeta, TimerOwners, TimerPausing, TimerQuerying, TimerRef,
+    TimerRescheduling, TimerTagging, TimerTicking, TimerWithThread,
+};
+use crate::wallclock::{Deadline, Wakeup, wall_clock_return, wall_clock_wait};
+use crossbeam_channel as channel;
+use hierarchical_hash_wheel_timer::{
+    ClosureTimer, OneShotClosureState, PeriodicClosureState, Timer, TimerReturn,
+};
+use std::sync::Mutex;
+use std::time::Duration;
 use uuid::Uuid;
 
+type TestTimerRef = TimerRef<Uuid, OneShotClosureState<Uuid>, PeriodicClosureState<Uuid>>;
+
+fn tick_timer() -> (
+    TimerWithThread<Uuid, OneShotClosureState<Uuid>, PeriodicClosureState<Uuid>>,
+    TestTimerRef,
+) {
+    let core = TimerWithThread::for_uuid_closures_sans_autotick().unwrap();
+    let timers = core.timer_ref();
+    (core, timers)
+}
+
+fn tick_n(timers: &mut TestTimerRef, n: usize) {
+    for _ in 0..n {
+        timers.tick();
+    }
+}
+
+/// Waits until the timer thread has handled every message sent before this call.
+fn sync(timers: &mut TestTimerRef) {
+    let (s, r) = channel::bounded(1);
+    timers.schedule_action_once(Uuid::new_v4(), Duration::ZERO, move |_| {
+        let _ = s.send(());
+    });
+    r.recv_timeout(Duration::from_secs(5)).unwrap();
+}
+
 #[test]
 fn should_reschedule_for_reschedule_marker() {
     let result = should_reschedule(Some("TIMER_RESCHEDULE".to_string()));
 
-    assert_eq!(result, TimerReturn::Reschedule(()));
+    assert_eq!(result, TimerReturn::Reschedule(NextFiring::Period));
 }
 
 #[test]
@@ -20,7 +67,47 @@ fn should_cancel_for_cancel_marker() {
 fn should_reschedule_by_default() {
     let result = should_reschedule(None);
 
-    assert_eq!(result, TimerReturn::Reschedule(()));
+    assert_eq!(result, TimerReturn::Reschedule(NextFiring::Period));
+}
+
+#[test]
+fn should_reschedule_at_the_period_after_returned_number() {
+    // procs that return TRUE or a count of something keep their period, only RT_TIMER_DELAY changes it
+    for number in [250.0, 2.0, 1.0, 0.0, -5.0] {
+        assert_eq!(
+            should_reschedule(number),
+            TimerReturn::Reschedule(NextFiring::Period)
+        );
+    }
+    assert_eq!(
+        should_reschedule(Some("RT_TIMER_DELAY:1".to_string())),
+        TimerReturn::Reschedule(NextFiring::After(Duration::from_millis(1)))
+    );
+}
+
+#[test]
+fn should_understand_every_protocol_return() {
+    let text = |s: &str| should_reschedule(Some(s.to_string()));
+
+    assert_eq!(text(CANCEL.text), TimerReturn::Cancel);
+    assert_eq!(should_reschedule(CANCEL.code as f32), TimerReturn::Cancel);
+    assert_eq!(text(PAUSE.text), TimerReturn::Reschedule(NextFiring::Pause));
+    assert_eq!(
+        should_reschedule(PAUSE.code as f32),
+        TimerReturn::Reschedule(NextFiring::Pause)
+    );
+    assert_eq!(
+        text("RT_TIMER_DELAY:1500"),
+        TimerReturn::Reschedule(NextFiring::After(Duration::from_millis(1500)))
+    );
+    assert_eq!(
+        text("RT_TIMER_DELAY:soon"),
+        TimerReturn::Reschedule(NextFiring::Period)
+    );
+    assert_eq!(
+        text("RT_TIMER_RESCHEDULE"),
+        TimerReturn::Reschedule(NextFiring::Period)
+    );
 }
 
 #[test]
@@ -32,9 +119,1287 @@ fn uuid_prefix_routes_to_timer_type() {
     assert!(matches!(byond_tick_id.timertype(), TimerType::ByondTick));
 }
 
+#[test]
+fn keyed_uuid_is_stable_and_routes_to_timer_type() {
+    let real_time_id = get_keyed_uuid(TimerType::RealTime, "mob_1_burn");
+    let byond_tick_id = get_keyed_uuid(TimerType::ByondTick, "mob_1_burn");
+
+    assert_eq!(
+        real_time_id,
+        get_keyed_uuid(TimerType::RealTime, "mob_1_burn")
+    );
+    assert_ne!(
+        real_time_id,
+        get_keyed_uuid(TimerType::RealTime, "mob_2_burn")
+    );
+    assert!(matches!(real_time_id.timertype(), TimerType::RealTime));
+    assert!(matches!(byond_tick_id.timertype(), TimerType::ByondTick));
+}
+
 #[test]
 fn unknown_uuid_prefix_routes_to_real_time() {
     let id = Uuid::from_bytes([42; 16]);
 
     assert!(matches!(id.timertype(), TimerType::RealTime));
 }
+
+#[test]
+fn paused_timer_keeps_remaining_time() {
+    let (_core, mut timers) = tick_timer();
+    let (s, r) = channel::unbounded();
+    let id = Uuid::new_v4();
+
+    timers.schedule_action_once(id, Duration::from_millis(5), move |_| {
+        let _ = s.send(());
+    });
+    tick_n(&mut timers, 2);
+    timers.pause(&id);
+    tick_n(&mut timers, 10);
+    sync(&mut timers);
+    assert!(r.try_recv().is_err());
+
+    timers.resume(&id);
+    tick_n(&mut timers, 2);
+    sync(&mut timers);
+    assert!(r.try_recv().is_err());
+
+    tick_n(&mut timers, 1);
+    sync(&mut timers);
+    assert!(r.try_recv().is_ok());
+}
+
+#[test]
+fn info_reports_remaining_time_and_period() {
+    let (_core, mut timers) = tick_timer();
+    let id = Uuid::new_v4();
+
+    timers.schedule_action_periodic(
+        id,
+        Duration::from_millis(5),
+        Duration::from_millis(10),
+        |_| TimerReturn::Reschedule(()),
+    );
+    tick_n(&mut timers, 2);
+    assert_eq!(
+        timers.info(&id).unwrap(),
+        Some(TimerInfo {
+            remaining: Duration::from_millis(3),
+            period: Some(Duration::from_millis(10)),
+            paused: false,
+        })
+    );
+
+    timers.pause(&id);
+    assert!(timers.info(&id).unwrap().is_some_and(|info| info.paused));
+
+    timers.cancel(&id);
+    assert_eq!(timers.info(&id).unwrap(), None);
+}
+
+#[test]
+fn rescheduled_timer_keeps_its_id() {
+    let (_core, mut timers) = tick_timer();
+    let (s, r) = channel::unbounded();
+    let id = Uuid::new_v4();
+
+    timers.schedule_action_periodic(
+        id,
+        Duration::from_millis(5),
+        Duration::from_millis(10),
+        move |id| {
+            let _ = s.send(id);
+            TimerReturn::Reschedule(())
+        },
+    );
+    timers.reschedule(
+        &id,
+        Duration::from_millis(20),
+        Some(Duration::from_millis(7)),
+    );
+    timers.snooze(&id, Duration::from_millis(3));
+    let info = timers.info(&id).unwrap().unwrap();
+    assert_eq!(info.remaining, Duration::from_millis(23));
+    assert_eq!(info.period, Some(Duration::from_millis(7)));
+
+    tick_n(&mut timers, 23);
+    sync(&mut timers);
+    assert_eq!(r.try_recv(), Ok(id));
+    assert_eq!(
+        timers.info(&id).unwrap().map(|info| info.remaining),
+        Some(Duration::from_millis(7))
+    );
+}
+
+#[test]
+fn tag_operations_only_touch_tagged_timers() {
+    let (_core, mut timers) = tick_timer();
+    let tagged = |tag: &str| TimerMeta {
+        tag: Some(tag.to_string()),
+        ..Default::default()
+    };
+    let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+    let delay = Duration::from_millis(5);
+
+    timers.schedule_once_with_meta(delay, OneShotClosureState::new(a, |_| ()), tagged("x"));
+    timers.schedule_once_with_meta(delay, OneShotClosureState::new(b, |_| ()), tagged("x"));
+    timers.schedule_once_with_meta(delay, OneShotClosureState::new(c, |_| ()), tagged("y"));
+
+    let mut listed = timers.list_tag("x").unwrap();
+    listed.sort();
+    let mut expected = vec![a, b];
+    expected.sort();
+    assert_eq!(listed, expected);
+
+    timers.pause_tag("x");
+    assert!(timers.info(&a).unwrap().is_some_and(|info| info.paused));
+    assert!(timers.info(&c).unwrap().is_some_and(|info| !info.paused));
+
+    timers.cancel_tag("x");
+    assert_eq!(timers.info(&a).unwrap(), None);
+    assert!(timers.list_tag("x").unwrap().is_empty());
+
+    tick_n(&mut timers, 5);
+    assert!(timers.list_tag("y").unwrap().is_empty());
+}
+
+#[test]
+fn cancelling_owner_drops_all_of_its_timers() {
+    let (_core, mut timers) = tick_timer();
+    let owned = |owner| TimerMeta {
+        owner: Some(owner),
+        ..Default::default()
+    };
+    let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+    let delay = Duration::from_millis(5);
+
+    timers.schedule_once_with_meta(delay, OneShotClosureState::new(a, |_| ()), owned(1));
+    timers.schedule_periodic_with_meta(
+        delay,
+        delay,
+        PeriodicClosureState::new(b, |_| TimerReturn::Reschedule(())),
+        owned(1),
+    );
+    timers.schedule_once_with_meta(delay, OneShotClosureState::new(c, |_| ()), owned(2));
+    assert_eq!(timers.list_owner(1).unwrap().len(), 2);
+
+    timers.cancel_owner(1);
+    assert!(timers.list_owner(1).unwrap().is_empty());
+    assert_eq!(timers.list_owner(2).unwrap(), vec![c]);
+    assert_eq!(timers.info(&b).unwrap(), None);
+}
+
+#[test]
+fn timers_drop_when_their_owner_is_gone_or_replaced() {
+    let identity = OwnerIdentity {
+        typepath: "/mob/living".to_string(),
+        uid: Some(("unique_id".to_string(), 42)),
+    };
+    let alive = OwnerState {
+        typepath: "/mob/living".to_string(),
+        uid: Some(42),
+        being_deleted: false,
+    };
+    let dropped = DroppedTimers::new();
+    let check = |current: Option<&OwnerState<i32>>| dropped.count_if(identity.is_gone(current));
+
+    assert!(!check(Some(&alive)));
+    assert!(check(None));
+    assert!(check(Some(&OwnerState {
+        being_deleted: true,
+        ..alive.clone()
+    })));
+    // the ref was reused by another datum
+    assert!(check(Some(&OwnerState {
+        typepath: "/obj/item".to_string(),
+        ..alive.clone()
+    })));
+    assert!(check(Some(&OwnerState {
+        uid: Some(43),
+        ..alive.clone()
+    })));
+    assert!(check(Some(&OwnerState {
+        uid: None,
+        ..alive.clone()
+    })));
+    assert_eq!(dropped.count(), 5);
+
+    // without a uid var, only the type tells datums apart
+    let untracked = OwnerIdentity {
+        uid: None,
+        ..identity.clone()
+    };
+    assert!(!untracked.is_gone(Some(&OwnerState {
+        uid: Some(43),
+        ..alive
+    })));
+}
+
+#[test]
+fn duplicate_policy_decides_which_timer_survives() {
+    let (_core, mut timers) = tick_timer();
+    let id = Uuid::new_v4();
+    let with_policy = |duplicate| TimerMeta {
+        duplicate,
+        ..Default::default()
+    };
+    let schedule = |timers: &mut TestTimerRef, delay, duplicate| {
+        let state = OneShotClosureState::new(id, |_| ());
+        timers.schedule_once_with_meta(Duration::from_millis(delay), state, with_policy(duplicate));
+    };
+    let remaining = |timers: &mut TestTimerRef| timers.info(&id).unwrap().unwrap().remaining;
+
+    schedule(&mut timers, 10, DuplicatePolicy::Replace);
+    schedule(&mut timers, 20, DuplicatePolicy::KeepExisting);
+    assert_eq!(remaining(&mut timers), Duration::from_millis(10));
+
+    schedule(&mut timers, 5, DuplicatePolicy::Extend);
+    assert_eq!(remaining(&mut timers), Duration::from_millis(10));
+    schedule(&mut timers, 30, DuplicatePolicy::Extend);
+    assert_eq!(remaining(&mut timers), Duration::from_millis(30));
+
+    schedule(&mut timers, 15, DuplicatePolicy::Replace);
+    assert_eq!(remaining(&mut timers), Duration::from_millis(15));
+}
+
+#[test]
+fn throttled_timer_fires_at_most_once_per_interval() {
+    let (_core, mut timers) = tick_timer();
+    let id = Uuid::new_v4();
+    let (s, r) = channel::unbounded();
+    let meta = TimerMeta {
+        duplicate: DuplicatePolicy::Throttle(Duration::from_millis(10)),
+        ..Default::default()
+    };
+    let schedule = |timers: &mut TestTimerRef, n: u32| {
+        let s = s.clone();
+        let state = OneShotClosureState::new(id, move |_| s.send(n).unwrap());
+        timers.schedule_once_with_meta(Duration::ZERO, state, meta.clone());
+    };
+
+    schedule(&mut timers, 1);
+    sync(&mut timers);
+    assert_eq!(r.try_recv(), Ok(1));
+
+    schedule(&mut timers, 2);
+    schedule(&mut timers, 3);
+    sync(&mut timers);
+    assert!(r.try_recv().is_err());
+    assert_eq!(
+        timers.info(&id).unwrap().unwrap().remaining,
+        Duration::from_millis(10)
+    );
+
+    tick_n(&mut timers, 10);
+    sync(&mut timers);
+    assert_eq!(r.try_recv(), Ok(3));
+    assert!(r.try_recv().is_err());
+}
+
+#[test]
+fn throttled_timer_keeps_its_turn_when_called_again_while_deferred() {
+    let (_core, mut timers) = tick_timer();
+    let id = Uuid::new_v4();
+    let (s, r) = channel::unbounded();
+    let meta = TimerMeta {
+        duplicate: DuplicatePolicy::Throttle(Duration::from_millis(10)),
+        priority: -1,
+        ..Default::default()
+    };
+    let schedule = |timers: &mut TestTimerRef, name: &'static str| {
+        let s = s.clone();
+        let state = OneShotClosureState::new(id, move |_| s.send(name).unwrap());
+        timers.schedule_once_with_meta(Duration::ZERO, state, meta.clone());
+    };
+    schedule(&mut timers, "throttled 1");
+    schedule(&mut timers, "throttled 2");
+    for name in ["other 1", "other 2"] {
+        let s = s.clone();
+        timers.schedule_action_once(Uuid::new_v4(), Duration::from_millis(10), move |_| {
+            s.send(name).unwrap()
+        });
+    }
+    sync(&mut timers);
+    timers.set_tick_budget(Some(Duration::ZERO));
+
+    tick_n(&mut timers, 10);
+    sync(&mut timers);
+    assert_eq!(
+        r.try_iter().collect::<Vec<_>>(),
+        vec!["throttled 1", "other 1"]
+    );
+
+    // the deferred call is neither fired right away nor pushed back behind a fresh interval
+    schedule(&mut timers, "throttled 3");
+    sync(&mut timers);
+    assert!(r.try_recv().is_err());
+
+    tick_n(&mut timers, 2);
+    sync(&mut timers);
+    assert_eq!(
+        r.try_iter().collect::<Vec<_>>(),
+        vec!["other 2", "throttled 3"]
+    );
+}
+
+#[test]
+fn periodic_timer_stops_at_its_repeat_limit() {
+    let (_core, mut timers) = tick_timer();
+    let (s, r) = channel::unbounded();
+    let periodic = |timers: &mut TestTimerRef, name, delay, meta| {
+        let s = s.clone();
+        let state = PeriodicClosureState::new(Uuid::new_v4(), move |_| {
+            s.send(name).unwrap();
+            TimerReturn::Reschedule(())
+        });
+        let period = Duration::from_millis(2);
+        timers.schedule_periodic_with_meta(Duration::from_millis(delay), period, state, meta);
+    };
+
+    let counted = TimerMeta {
+        max_firings: Some(3),
+        ..Default::default()
+    };
+    periodic(&mut timers, "counted", 0, counted);
+    let ending = TimerMeta {
+        end_after: Some(Duration::from_millis(5)),
+        ..Default::default()
+    };
+    periodic(&mut timers, "ending", 0, ending);
+    // an end time that has already passed, like an `end_at` in the past
+    let ended = TimerMeta {
+        end_after: Some(Duration::ZERO),
+        ..Default::default()
+    };
+    periodic(&mut timers, "ended", 3, ended);
+
+    tick_n(&mut timers, 20);
+    sync(&mut timers);
+    let fired: Vec<_> = r.try_iter().collect();
+    assert_eq!(fired.iter().filter(|&&n| n == "counted").count(), 3);
+    // fires at 0, 2 and 4, the firing at 6 would be past the end
+    assert_eq!(fired.iter().filter(|&&n| n == "ending").count(), 3);
+    assert!(!fired.contains(&"ended"));
+}
+
+#[test]
+fn periodic_closure_picks_its_next_delay() {
+    let core = TimerWithThread::for_uuid_adaptive_closures_sans_autotick().unwrap();
+    let mut timers = core.timer_ref();
+    let id = Uuid::new_v4();
+    let mut delays = vec![
+        NextFiring::Period,
+        NextFiring::After(Duration::ZERO),
+        NextFiring::After(Duration::from_millis(3)),
+    ];
+    let state =
+        AdaptiveClosureState::new(id, move |_| TimerReturn::Reschedule(delays.pop().unwrap()));
+    timers.schedule_periodic(Duration::from_millis(1), Duration::from_millis(10), state);
+    let remaining = |timers: &mut TimerRef<_, LateClosureState<Uuid>, _>| {
+        timers.info(&id).unwrap().unwrap().remaining
+    };
+
+    timers.tick();
+    assert_eq!(remaining(&mut timers), Duration::from_millis(3));
+    for _ in 0..3 {
+        timers.tick();
+    }
+    // zero is bumped to the shortest possible delay
+    assert_eq!(remaining(&mut timers), Duration::from_millis(1));
+    timers.tick();
+    assert_eq!(remaining(&mut timers), Duration::from_millis(10));
+}
+
+#[test]
+fn periodic_closure_can_pause_itself() {
+    let core = TimerWithThread::for_uuid_adaptive_closures_sans_autotick().unwrap();
+    let mut timers = core.timer_ref();
+    let id = Uuid::new_v4();
+    let state = AdaptiveClosureState::new(id, |_| TimerReturn::Reschedule(NextFiring::Pause));
+    timers.schedule_periodic(Duration::from_millis(1), Duration::from_millis(10), state);
+
+    timers.tick();
+    let info = timers.info(&id).unwrap().unwrap();
+    assert!(info.paused);
+    assert_eq!(info.remaining, Duration::from_millis(10));
+}
+
+#[test]
+fn tick_budget_defers_due_timers_to_later_ticks() {
+    let (_core, mut timers) = tick_timer();
+    let (s, r) = channel::unbounded();
+    let ids: Vec<_> = (0..3).map(|_| Uuid::new_v4()).collect();
+    for &id in &ids {
+        let s = s.clone();
+        timers.schedule_action_once(id, Duration::from_millis(1), move |id| s.send(id).unwrap());
+    }
+    timers.set_tick_budget(Some(Duration::ZERO));
+
+    timers.tick();
+    sync(&mut timers);
+    assert_eq!(r.try_iter().count(), 1);
+
+    // a deferred timer can still be cancelled
+    let deferred = ids
+        .iter()
+        .find(|id| timers.info(id).unwrap().is_some())
+        .unwrap();
+    timers.cancel(deferred);
+    tick_n(&mut timers, 2);
+    sync(&mut timers);
+    assert_eq!(r.try_iter().count(), 1);
+    assert!(ids.iter().all(|id| timers.info(id).unwrap().is_none()));
+}
+
+#[test]
+fn due_timers_run_in_priority_order() {
+    let (_core, mut timers) = tick_timer();
+    let (s, r) = channel::unbounded();
+    for priority in [0, 10, -5, 3] {
+        let s = s.clone();
+        let state = OneShotClosureState::new(Uuid::new_v4(), move |_| s.send(priority).unwrap());
+        let meta = TimerMeta {
+            priority,
+            ..Default::default()
+        };
+        timers.schedule_once_with_meta(Duration::from_millis(2), state, meta);
+    }
+
+    tick_n(&mut timers, 2);
+    sync(&mut timers);
+    assert_eq!(r.try_iter().collect::<Vec<_>>(), vec![10, 3, 0, -5]);
+}
+
+#[test]
+fn deferred_timers_wait_behind_higher_priority_timers_due_later() {
+    let (_core, mut timers) = tick_timer();
+    let (s, r) = channel::unbounded();
+    let mut schedule = |name: &'static str, priority, delay| {
+        let s = s.clone();
+        let state = OneShotClosureState::new(Uuid::new_v4(), move |_| s.send(name).unwrap());
+        let meta = TimerMeta {
+            priority,
+            ..Default::default()
+        };
+        timers.schedule_once_with_meta(Duration::from_millis(delay), state, meta);
+    };
+    for name in ["cosmetic 1", "cosmetic 2", "cosmetic 3"] {
+        schedule(name, 0, 1);
+    }
+    schedule("combat", 10, 2);
+    timers.set_tick_budget(Some(Duration::ZERO));
+
+    // each tick only runs one timer, and the leftovers of the first tick don't hold up the combat timer
+    tick_n(&mut timers, 4);
+    sync(&mut timers);
+    assert_eq!(
+        r.try_iter().collect::<Vec<_>>(),
+        vec!["cosmetic 1", "combat", "cosmetic 2", "cosmetic 3"]
+    );
+}
+
+#[test]
+fn timers_with_the_same_deadline_fire_in_scheduling_order() {
+    let (_core, mut timers) = tick_timer();
+    let (s, r) = channel::unbounded();
+    for n in 0..50 {
+        let s = s.clone();
+        timers.schedule_action_once(Uuid::new_v4(), Duration::from_millis(3), move |_| {
+            s.send(n).unwrap()
+        });
+    }
+
+    tick_n(&mut timers, 3);
+    sync(&mut timers);
+    assert_eq!(
+        r.try_iter().collect::<Vec<_>>(),
+        (0..50).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn fixed_rate_timers_do_not_drift_behind_slow_callbacks() {
+    let interval = |period_mode| {
+        let clock = ManualClock::new();
+        let core =
+            TimerWithThread::for_uuid_closures_with_time_source(TimeSource::Manual(clock.clone()))
+                .unwrap();
+        let mut timers = core.timer_ref();
+        let (s, r) = channel::unbounded();
+        let callback_clock = clock.clone();
+        let state = PeriodicClosureState::new(Uuid::new_v4(), move |_| {
+            s.send(callback_clock.elapsed()).unwrap();
+            // the callback takes 60ms
+            callback_clock.advance(Duration::from_millis(60));
+            TimerReturn::Reschedule(())
+        });
+        let meta = TimerMeta {
+            period_mode,
+            max_firings: Some(2),
+            ..Default::default()
+        };
+        let period = Duration::from_millis(100);
+        timers.schedule_periodic_with_meta(Duration::ZERO, period, state, meta);
+
+        let first = r.recv_timeout(Duration::from_secs(5)).unwrap();
+        for _ in 0..50 {
+            clock.advance(Duration::from_millis(10));
+            // the second query is only answered after the thread ticked through the time advanced before the first
+            timers.list_tag("").unwrap();
+            timers.list_tag("").unwrap();
+            if let Ok(second) = r.try_recv() {
+                return second - first;
+            }
+        }
+        panic!("the timer did not fire a second time");
+    };
+
+    // a fixed delay is measured from when the callback finished, a fixed rate from when the timer was due
+    assert_eq!(interval(PeriodMode::FixedDelay), Duration::from_millis(160));
+    assert_eq!(interval(PeriodMode::FixedRate), Duration::from_millis(100));
+}
+
+#[test]
+fn periodic_timers_catch_up_on_missed_firings_by_policy() {
+    let firings = |catch_up| {
+        let core = TimerWithThread::for_uuid_adaptive_closures_sans_autotick().unwrap();
+        let mut timers = core.timer_ref();
+        timers.set_tick_budget(Some(Duration::ZERO));
+        // one-shot timers firing one per tick keep the periodic timer waiting until tick 6
+        for _ in 0..5 {
+            let state = LateClosureState::new(Uuid::new_v4(), |_, _| ());
+            let meta = TimerMeta {
+                priority: 1,
+                ..Default::default()
+            };
+            timers.schedule_once_with_meta(Duration::from_millis(1), state, meta);
+        }
+        let (s, r) = channel::unbounded();
+        let id = Uuid::new_v4();
+        let state = AdaptiveClosureState::reporting_skipped(id, move |_, skipped| {
+            s.send(skipped).unwrap();
+            TimerReturn::Reschedule(NextFiring::Period)
+        });
+        let meta = TimerMeta {
+            period_mode: PeriodMode::FixedRate,
+            catch_up: Some(catch_up),
+            ..Default::default()
+        };
+        let period = Duration::from_millis(2);
+        timers.schedule_periodic_with_meta(Duration::from_millis(1), period, state, meta);
+
+        let mut firings = Vec::new();
+        for tick in 1..=10 {
+            timers.tick();
+            timers.info(&id).unwrap();
+            firings.extend(r.try_iter().map(|skipped| (tick, skipped)));
+        }
+        firings
+    };
+
+    let all = [(6, 0), (7, 0), (8, 0), (9, 0), (10, 0)];
+    assert_eq!(firings(CatchUpPolicy::FireAll), all);
+    assert_eq!(firings(CatchUpPolicy::FireOnce), [(6, 2), (7, 0), (9, 0)]);
+    assert_eq!(firings(CatchUpPolicy::Skip), [(7, 3), (9, 0)]);
+}
+
+#[test]
+fn one_shot_timers_past_their_max_lateness_fire_late() {
+    let core = TimerWithThread::for_uuid_adaptive_closures_sans_autotick().unwrap();
+    let mut timers = core.timer_ref();
+    timers.set_tick_budget(Some(Duration::ZERO));
+    // one-shot timers firing one per tick keep the others waiting until tick 6
+    for _ in 0..5 {
+        let state = LateClosureState::new(Uuid::new_v4(), |_, _| ());
+        let meta = TimerMeta {
+            priority: 1,
+            ..Default::default()
+        };
+        timers.schedule_once_with_meta(Duration::from_millis(1), state, meta);
+    }
+    let (s, r) = channel::unbounded();
+    for max_lateness in [2, 10] {
+        let s = s.clone();
+        let state = LateClosureState::new(Uuid::new_v4(), move |_, lateness| {
+            s.send((max_lateness, lateness)).unwrap();
+        });
+        let meta = TimerMeta {
+            max_lateness: Some(Duration::from_millis(max_lateness)),
+            ..Default::default()
+        };
+        timers.schedule_once_with_meta(Duration::from_millis(1), state, meta);
+    }
+    for _ in 0..7 {
+        timers.tick();
+    }
+    // answered once the ticks have been handled
+    timers.info(&Uuid::new_v4()).unwrap();
+
+    let fired: Vec<_> = r.try_iter().collect();
+    assert_eq!(fired, [(2, Some(Duration::from_millis(5))), (10, None)]);
+}
+
+static CLOCK_JUMPS: Mutex<Vec<ClockJump>> = Mutex::new(Vec::new());
+
+#[test]
+fn clock_jumps_fire_due_timers_or_shift_them() {
+    let fired_at = |policy| {
+        let clock = ManualClock::new();
+        let core =
+            TimerWithThread::for_uuid_closures_with_time_source(TimeSource::Manual(clock.clone()))
+                .unwrap();
+        let mut timers = core.timer_ref();
+        timers.set_clock_jump_policy(policy, Duration::from_millis(50));
+        timers.on_clock_jump(|jump| CLOCK_JUMPS.lock().unwrap().push(jump));
+        let (s, r) = channel::bounded(1);
+        let fired_clock = clock.clone();
+        timers.schedule_action_once(Uuid::new_v4(), Duration::from_millis(30), move |_| {
+            s.send(fired_clock.elapsed()).unwrap();
+        });
+        timers.list_tag("").unwrap();
+
+        // real time jumps well past the timer, like after the process was suspended, and further than
+        // the thread catches up with in one go
+        clock.advance(Duration::from_millis(2_500));
+        for _ in 0..10 {
+            timers.list_tag("").unwrap();
+            timers.list_tag("").unwrap();
+            if let Ok(fired_at) = r.try_recv() {
+                return fired_at;
+            }
+            clock.advance(Duration::from_millis(10));
+        }
+        panic!("the timer did not fire");
+    };
+
+    assert_eq!(
+        fired_at(ClockJumpPolicy::FireDue),
+        Duration::from_millis(2_500)
+    );
+    assert_eq!(
+        fired_at(ClockJumpPolicy::Shift),
+        Duration::from_millis(2_530)
+    );
+
+    let jumps = CLOCK_JUMPS.lock().unwrap();
+    let summary: Vec<_> = jumps.iter().map(|j| (j.policy, j.gap, j.fired)).collect();
+    let gap = Duration::from_millis(2_500);
+    assert_eq!(
+        summary,
+        [
+            (ClockJumpPolicy::FireDue, gap, 1),
+            (ClockJumpPolicy::Shift, gap, 0)
+        ]
+    );
+}
+
+#[test]
+fn wall_clock_timers_recheck_the_clock_halfway_to_far_deadlines() {
+    assert_eq!(wall_clock_wait(60_000), Duration::from_secs(30));
+    assert_eq!(wall_clock_wait(1_000), Duration::from_secs(1));
+    assert_eq!(wall_clock_wait(0), Duration::from_millis(1));
+}
+
+#[test]
+fn wall_clock_timers_wait_for_past_and_far_future_deadlines() {
+    let now = utc_ms(19_781, 12, 0);
+
+    // a deadline in the past fires on the next tick, and is late by how far it's in the past
+    let past = Deadline(now - 90_000);
+    assert_eq!(past.first_wait(now), Duration::from_millis(1));
+    assert_eq!(past.wake(now), Wakeup::Due(90_000));
+    assert_eq!(Deadline(now).wake(now), Wakeup::Due(0));
+
+    // a deadline a year ahead is approached in halves, rechecking the clock each time
+    let year = 365 * 24 * 60 * 60_000;
+    let far = Deadline(now + year);
+    assert_eq!(far.first_wait(now), Duration::from_millis(year / 2));
+    assert_eq!(
+        far.wake(now + year - 500),
+        Wakeup::Wait(Duration::from_millis(500))
+    );
+    assert_eq!(far.wake(now + year + 3), Wakeup::Due(3));
+}
+
+#[test]
+fn wall_clock_timers_keep_to_their_deadlines_whatever_the_proc_returns() {
+    let now = utc_ms(19_781, 12, 0);
+    let next = now + 600;
+    let mut deadline = Deadline(now);
+    let delayed = TimerReturn::Reschedule(NextFiring::After(Duration::from_secs(3_600)));
+
+    assert_eq!(
+        deadline.after_firing(now, delayed, Some(next)),
+        TimerReturn::Reschedule(NextFiring::After(Duration::from_millis(600)))
+    );
+    assert_eq!(deadline, Deadline(next));
+    assert_eq!(
+        deadline.after_firing(next, TimerReturn::Cancel, Some(next + 600)),
+        TimerReturn::Cancel
+    );
+    let period = TimerReturn::Reschedule(NextFiring::Period);
+    assert_eq!(
+        deadline.after_firing(next, period, None),
+        TimerReturn::Cancel
+    );
+
+    // queued procs are applied after the timer moved on, so their delays are ignored as well
+    assert_eq!(wall_clock_return(delayed), period);
+    assert_eq!(wall_clock_return(TimerReturn::Cancel), TimerReturn::Cancel);
+}
+
+#[test]
+fn wall_clock_timers_pause_the_same_way_under_every_dispatch_mode() {
+    let now = utc_ms(19_781, 12, 0);
+    let next = now + 600;
+    let pause = TimerReturn::Reschedule(NextFiring::Pause);
+
+    // called on the timer thread, the proc's pause is returned to the wheel with the next deadline set
+    let mut deadline = Deadline(now);
+    assert_eq!(deadline.after_firing(now, pause, Some(next)), pause);
+    assert_eq!(deadline, Deadline(next));
+
+    // queued, the timer moves on to the next deadline as if the proc returned nothing, and then pauses
+    let mut deadline = Deadline(now);
+    let period = TimerReturn::Reschedule(NextFiring::Period);
+    deadline.after_firing(now, period, Some(next));
+    assert_eq!(deadline, Deadline(next));
+    assert_eq!(wall_clock_return(pause), pause);
+
+    // a pause on the last deadline still stops the timer
+    assert_eq!(
+        Deadline(now).after_firing(now, pause, None),
+        TimerReturn::Cancel
+    );
+}
+
+/// UNIX milliseconds of a UTC date and time
+fn utc_ms(days_since_epoch: u64, hour: u64, minute: u64) -> u64 {
+    ((days_since_epoch * 24 + hour) * 60 + minute) * 60_000
+}
+
+#[test]
+fn cron_schedules_find_their_next_occurrence() {
+    // 2024-02-28 was a Wednesday, 19781 days after the UNIX epoch
+    let feb_28 = 19_781;
+    let next = |expr: &str, after: u64| expr.parse::<CronSchedule>().unwrap().next_after(after);
+
+    let six_hourly = next("0 */6 * * *", utc_ms(feb_28, 7, 30));
+    assert_eq!(six_hourly, Some(utc_ms(feb_28, 12, 0)));
+    // occurrences are strictly after the given time
+    let six_hourly = next("0 */6 * * *", utc_ms(feb_28, 12, 0));
+    assert_eq!(six_hourly, Some(utc_ms(feb_28, 18, 0)));
+    // leap days, the first one being 1972-02-29
+    assert_eq!(next("15 3 29 feb *", 0), Some(utc_ms(789, 3, 15)));
+    assert_eq!(
+        next("15 3 29 feb *", utc_ms(feb_28, 0, 0)),
+        Some(utc_ms(feb_28 + 1, 3, 15))
+    );
+    // Sunday as 7, across the month boundary
+    assert_eq!(
+        next("0 9 * * 7", utc_ms(feb_28, 0, 0)),
+        Some(utc_ms(feb_28 + 4, 9, 0))
+    );
+    // either the day of month or the day of week
+    let first_or_thursday = next("0 0 1 * thu", utc_ms(feb_28, 0, 0));
+    assert_eq!(first_or_thursday, Some(utc_ms(feb_28 + 1, 0, 0)));
+    let first_or_thursday = next("0 0 1 * thu", utc_ms(feb_28 + 1, 0, 0));
+    assert_eq!(first_or_thursday, Some(utc_ms(feb_28 + 2, 0, 0)));
+    assert_eq!(next("0 0 30 2 *", 0), None);
+}
+
+#[test]
+fn cron_fields_matching_every_day_do_not_restrict_the_other_day_field() {
+    // 2024-02-28 was a Wednesday, 19781 days after the UNIX epoch
+    let feb_28 = 19_781;
+    let next = |expr: &str| {
+        let schedule = expr.parse::<CronSchedule>().unwrap();
+        schedule.next_after(utc_ms(feb_28, 0, 0))
+    };
+
+    // only the first of the month, rather than the first or any day of the week
+    let march_1 = Some(utc_ms(feb_28 + 2, 0, 0));
+    assert_eq!(next("0 0 1 * */1"), march_1);
+    assert_eq!(next("0 0 1 * 0-6"), march_1);
+    assert_eq!(next("0 0 1 * sun-7"), march_1);
+    // only Mondays, rather than Mondays or any day of the month
+    let monday = Some(utc_ms(feb_28 + 5, 0, 0));
+    assert_eq!(next("0 0 */1 * mon"), monday);
+    assert_eq!(next("0 0 1-31 * mon"), monday);
+}
+
+#[test]
+fn cron_schedules_reject_invalid_expressions() {
+    let parse = |expr: &str| expr.parse::<CronSchedule>();
+
+    assert_eq!(parse("0 */6 * *"), Err(CronError::FieldCount(4)));
+    for (expr, field, value) in [
+        ("60 * * * *", "minute", "60"),
+        ("* 1-25 * * *", "hour", "1-25"),
+        ("* * 0 * *", "day of month", "0"),
+        ("* * * 5-2 *", "month", "5-2"),
+        ("* * * * */0", "day of week", "*/0"),
+        ("* * * * someday", "day of week", "someday"),
+    ] {
+        let error = CronError::InvalidField {
+            field,
+            value: value.to_string(),
+        };
+        assert_eq!(parse(expr), Err(error), "{expr}");
+    }
+    assert!(parse("*/15 0-6,18-23 1,15 jan-jun mon-fri").is_ok());
+}
+
+#[test]
+fn daily_schedules_follow_daylight_saving_transitions() {
+    // Helsinki moved from UTC+2 to UTC+3 at 03:00 local time on 2024-03-31, and back at 04:00 on 2024-10-27
+    let mar_30 = 19_812;
+    let oct_26 = 20_022;
+    let next = |time: &str, after: u64| {
+        DailySchedule::new(time, "Europe/Helsinki")
+            .unwrap()
+            .next_after(after)
+    };
+
+    assert_eq!(
+        next("06:00", utc_ms(mar_30, 0, 0)),
+        Some(utc_ms(mar_30, 4, 0))
+    );
+    assert_eq!(
+        next("06:00", utc_ms(mar_30, 4, 0)),
+        Some(utc_ms(mar_30 + 1, 3, 0))
+    );
+    // skipped by the transition, fires an hour later
+    assert_eq!(
+        next("03:30", utc_ms(mar_30, 12, 0)),
+        Some(utc_ms(mar_30 + 1, 1, 30))
+    );
+    // happens twice, fires the first time only
+    assert_eq!(
+        next("03:30", utc_ms(oct_26, 12, 0)),
+        Some(utc_ms(oct_26 + 1, 0, 30))
+    );
+    assert_eq!(
+        next("03:30", utc_ms(oct_26 + 1, 0, 30)),
+        Some(utc_ms(oct_26 + 2, 1, 30))
+    );
+
+    let utc = DailySchedule::new("23:59:30", "UTC").unwrap();
+    assert_eq!(
+        utc.next_after(utc_ms(mar_30, 0, 0)),
+        Some(utc_ms(mar_30, 23, 59) + 30_000)
+    );
+}
+
+#[test]
+fn daily_schedules_reject_invalid_times_and_zones() {
+    for time in [
+        "24:00",
+        "6",
+        "06:60",
+        "06:00:60",
+        "06:00:00:00",
+        "6am",
+        "-1:00",
+        "",
+    ] {
+        let error = DailyError::InvalidTime(time.to_string());
+        assert_eq!(
+            DailySchedule::new(time, "UTC").unwrap_err(),
+            error,
+            "{time}"
+        );
+    }
+    let error = DailyError::UnknownTimeZone("Mars/Olympus_Mons".to_string());
+    assert_eq!(
+        DailySchedule::new("06:00", "Mars/Olympus_Mons").unwrap_err(),
+        error
+    );
+    assert!(DailySchedule::new("6:05", " America/New_York ").is_ok());
+}
+
+#[test]
+fn duration_strings_parse_to_milliseconds() {
+    let millis = |text: &str| parse_duration(text).map(|duration| duration.as_millis());
+
+    assert_eq!(millis("5m30s"), Ok(330_000));
+    assert_eq!(millis("1.5h"), Ok(5_400_000));
+    assert_eq!(millis(" 2d 12h "), Ok(216_000_000));
+    assert_eq!(millis("250 ms"), Ok(250));
+    assert_eq!(millis("1S 500MS"), Ok(1_500));
+    assert_eq!(millis("PT10M"), Ok(600_000));
+    assert_eq!(millis("P1DT12H"), Ok(129_600_000));
+    assert_eq!(millis("P1W"), Ok(604_800_000));
+    assert_eq!(millis("PT0.5S"), Ok(500));
+    assert_eq!(millis("pt1h30m"), Ok(5_400_000));
+}
+
+#[test]
+fn duration_strings_report_what_is_wrong_with_them() {
+    let error = |text: &str| parse_duration(text).unwrap_err();
+
+    assert_eq!(error("  "), DurationError::Empty);
+    assert_eq!(
+        error("1500"),
+        DurationError::MissingUnit("1500".to_string())
+    );
+    assert_eq!(error("5m 30"), DurationError::MissingUnit("30".to_string()));
+    assert_eq!(
+        error("5 minutes"),
+        DurationError::UnknownUnit("minutes".to_string())
+    );
+    assert_eq!(
+        error("-5s"),
+        DurationError::InvalidNumber("-5s".to_string())
+    );
+    assert_eq!(
+        error("1.2.3s"),
+        DurationError::InvalidNumber("1.2.3s".to_string())
+    );
+    for iso in ["P", "PT", "PT5", "P1H", "PT1D", "PT1S1M", "PT1M1M", "P T1M"] {
+        assert_eq!(
+            error(iso),
+            DurationError::InvalidIso(iso.to_string()),
+            "{iso}"
+        );
+    }
+    assert_eq!(error("P1M"), DurationError::CalendarUnit("P1M".to_string()));
+    assert_eq!(
+        error("P1Y2D"),
+        DurationError::CalendarUnit("P1Y2D".to_string())
+    );
+    assert_eq!(error("99999999999999999999d"), DurationError::TooLong);
+    assert!(
+        error("5x")
+            .to_string()
+            .contains("expected ms, s, m, h or d")
+    );
+}
+
+#[test]
+fn duration_strings_convert_to_whole_ticks() {
+    let tick = Duration::from_millis(100);
+
+    assert_eq!(
+        duration_in_ticks(Duration::from_secs(5), tick),
+        Duration::from_millis(50)
+    );
+    // rounds up, so a timer never fires early
+    assert_eq!(
+        duration_in_ticks(Duration::from_millis(150), tick),
+        Duration::from_millis(2)
+    );
+    let fast_tick = Duration::from_millis(25);
+    assert_eq!(
+        duration_in_ticks(Duration::from_secs(1), fast_tick),
+        Duration::from_millis(40)
+    );
+}
+
+/// The tick a timer fires on, scheduled on tick 3 with the delay `delay` picks for that tick count,
+/// or `None` if it fires before any tick.
+fn fired_on_tick(delay: impl Fn(u32) -> u64) -> Option<u32> {
+    let (_core, mut timers) = tick_timer();
+    let (s, r) = channel::unbounded();
+    let mut tick_count = 0;
+    for _ in 0..3 {
+        timers.tick();
+        tick_count += 1;
+    }
+    let delay = delay(tick_count);
+    timers.schedule_action_once(Uuid::new_v4(), Duration::from_millis(delay), move |_| {
+        s.send(()).unwrap()
+    });
+    sync(&mut timers);
+    if r.try_recv().is_ok() {
+        return None;
+    }
+    while tick_count < 20 {
+        timers.tick();
+        tick_count += 1;
+        sync(&mut timers);
+        if r.try_recv().is_ok() {
+            return Some(tick_count);
+        }
+    }
+    None
+}
+
+#[test]
+fn timers_scheduled_for_an_absolute_tick_fire_on_that_tick() {
+    let fired_on = |target_tick| fired_on_tick(|tick_count| ticks_until(target_tick, tick_count));
+
+    assert_eq!(fired_on(7), Some(7));
+    assert_eq!(fired_on(4), Some(4));
+    // ticks that already passed, the current one included, fire on the next tick
+    assert_eq!(fired_on(3), Some(4));
+    assert_eq!(fired_on(1), Some(4));
+}
+
+#[test]
+fn delays_until_absolute_ticks_never_fire_before_the_next_tick() {
+    assert_eq!(ticks_until(7, 3), 4);
+    assert_eq!(ticks_until(4, 3), 1);
+    assert_eq!(ticks_until(3, 3), 1);
+    assert_eq!(ticks_until(0, 3), 1);
+    assert_eq!(ticks_until(u32::MAX, 0), u64::from(u32::MAX));
+}
+
+#[test]
+fn timers_scheduled_for_a_world_time_fire_on_the_first_tick_at_or_after_it() {
+    // at 20 ticks per second, world.time is 1.5 on tick 3
+    let fired_on = |target_time| {
+        fired_on_tick(|tick_count| {
+            let target_tick = world_time_tick(target_time, 1.5, 0.5, tick_count);
+            ticks_until(target_tick, tick_count)
+        })
+    };
+
+    assert_eq!(fired_on(2.5), Some(5));
+    // a time between two ticks fires on the later one
+    assert_eq!(fired_on(2.2), Some(5));
+    // times that already passed, the current one included, fire on the next tick
+    assert_eq!(fired_on(1.5), Some(4));
+    assert_eq!(fired_on(0.0), Some(4));
+}
+
+#[test]
+fn world_times_round_up_to_whole_ticks() {
+    assert_eq!(world_time_tick(101.5, 100.0, 0.5, 200), 203);
+    assert_eq!(world_time_tick(101.1, 100.0, 0.5, 200), 203);
+    // f32 rounding errors don't push a whole number of ticks to the next one
+    assert_eq!(world_time_tick(0.3, 0.0, 0.1, 0), 3);
+    assert_eq!(world_time_tick(100.0, 100.0, 0.5, 200), 200);
+    assert_eq!(world_time_tick(50.0, 100.0, 0.5, 200), 200);
+    assert_eq!(world_time_tick(f32::NAN, 100.0, 0.5, 200), 200);
+    // a tick_lag that makes no sense counts a decisecond per tick
+    assert_eq!(world_time_tick(103.0, 100.0, 0.0, 200), 203);
+}
+
+static DUE: Mutex<DueQueue<Uuid, &str>> = Mutex::new(DueQueue::new());
+
+#[test]
+fn due_timers_are_queued_by_the_time_a_tick_is_acknowledged() {
+    let (_core, mut timers) = tick_timer();
+    let id = Uuid::new_v4();
+    // queues the timer, like the closures of timers do in main thread dispatch mode
+    timers.schedule_action_once(id, Duration::from_millis(1), |id| {
+        DUE.lock().unwrap().push(id, "tick timer");
+    });
+
+    timers.tick_and_wait().unwrap();
+    let mut ran = Vec::new();
+    run_queued(&DUE, None, |due_id, name| {
+        if due_id == id {
+            ran.push(name);
+        }
+    });
+    assert_eq!(ran, ["tick timer"]);
+}
+
+#[test]
+fn queued_timers_run_in_the_order_they_came_due() {
+    let queue = Mutex::new(DueQueue::new());
+    let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+    for (id, name) in [(a, "a1"), (b, "b"), (a, "a2"), (c, "c")] {
+        queue.lock().unwrap().push(id, name);
+    }
+    queue.lock().unwrap().forget(&b);
+
+    let mut ran = Vec::new();
+    let count = run_queued(&queue, None, |id, name| {
+        ran.push(name);
+        // a proc cancelling a timer that is queued further back
+        if id == a {
+            queue.lock().unwrap().forget(&c);
+        }
+        // and one scheduling a timer that comes due right away, left for the next run
+        queue.lock().unwrap().push(Uuid::new_v4(), "later");
+    });
+    assert_eq!(ran, ["a1", "a2"]);
+    assert_eq!(count, 2);
+    assert_eq!(queue.lock().unwrap().len(), 2);
+}
+
+#[test]
+fn forgotten_timers_only_lose_the_calls_queued_before() {
+    let mut queue = DueQueue::new();
+    let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+    for (id, name) in [(a, "a1"), (b, "b1"), (a, "a2")] {
+        queue.push(id, name);
+    }
+    queue.forget(&a);
+    // forgetting again, or a timer with nothing queued, changes nothing
+    queue.forget(&a);
+    queue.forget(&Uuid::new_v4());
+    assert_eq!(queue.len(), 1);
+
+    // a replacement with the same ID comes due after the cancel
+    queue.push(a, "a3");
+    queue.forget(&b);
+    queue.push(b, "b2");
+    assert_eq!(queue.len(), 2);
+
+    let mut ran = Vec::new();
+    while let Some((_, name)) = queue.pop_front() {
+        ran.push(name);
+    }
+    assert_eq!(ran, ["a3", "b2"]);
+    assert!(queue.is_empty());
+}
+
+#[test]
+fn queued_timers_keep_to_the_tick_budget() {
+    let queue = Mutex::new(DueQueue::new());
+    for n in 0..3 {
+        queue.lock().unwrap().push(Uuid::new_v4(), n);
+    }
+
+    let mut ran = Vec::new();
+    // at least one runs, however small the budget
+    assert_eq!(
+        run_queued(&queue, Some(Duration::ZERO), |_, n| ran.push(n)),
+        1
+    );
+    assert_eq!(
+        run_queued(&queue, Some(Duration::from_secs(5)), |_, n| ran.push(n)),
+        2
+    );
+    assert_eq!(ran, [0, 1, 2]);
+}
+
+#[test]
+fn due_queue_reports_when_nothing_takes_timers_out_of_it() {
+    let mut queue = DueQueue::new();
+    let mut reported = Vec::new();
+    for n in 0..DUE_QUEUE_WARNING * 2 {
+        reported.extend(queue.push(n, ()));
+    }
+    assert_eq!(reported, [DUE_QUEUE_WARNING + 1]);
+
+    // reports again once it has been drained and grows past the limit again
+    while queue.len() > DUE_QUEUE_WARNING / 4 {
+        queue.pop_front();
+    }
+    reported.clear();
+    for n in 0..DUE_QUEUE_WARNING {
+        reported.extend(queue.push(n, ()));
+    }
+    assert_eq!(reported.len(), 1);
+}
+
+#[derive(Default)]
+struct RecordedTimers(Vec<(&'static str, u32, Option<Duration>)>);
+
+impl DueTimers<u32> for RecordedTimers {
+    fn cancel(&mut self, id: u32) {
+        self.0.push(("cancel", id, None));
+    }
+
+    fn pause(&mut self, id: u32) {
+        self.0.push(("pause", id, None));
+    }
+
+    fn delay(&mut self, id: u32, delay: Duration) {
+        self.0.push(("delay", id, Some(delay)));
+    }
+}
+
+#[test]
+fn returns_of_queued_periodic_timers_are_applied_afterwards() {
+    let mut timers = RecordedTimers::default();
+    let delay = Duration::from_millis(250);
+
+    apply_periodic_return(&mut timers, 1, TimerReturn::Reschedule(NextFiring::Period));
+    apply_periodic_return(&mut timers, 2, TimerReturn::Cancel);
+    apply_periodic_return(&mut timers, 3, TimerReturn::Reschedule(NextFiring::Pause));
+    apply_periodic_return(
+        &mut timers,
+        4,
+        TimerReturn::Reschedule(NextFiring::After(delay)),
+    );
+    assert_eq!(
+        timers.0,
+        [
+            ("cancel", 2, None),
+            ("pause", 3, None),
+            ("delay", 4, Some(delay))
+        ]
+    );
+}
+
+static CANCELLED: Mutex<Vec<Uuid>> = Mutex::new(Vec::new());
+
+#[test]
+fn every_way_of_cancelling_a_timer_is_reported() {
+    let (_core, mut timers) = tick_timer();
+    timers.on_cancel(|id| CANCELLED.lock().unwrap().push(*id));
+    let meta = |tag: &str, owner| TimerMeta {
+        tag: Some(tag.to_string()),
+        owner: Some(owner),
+        ..Default::default()
+    };
+    let (single, tagged, owned, keyed) = (
+        Uuid::new_v4(),
+        Uuid::new_v4(),
+        Uuid::new_v4(),
+        Uuid::new_v4(),
+    );
+    let delay = Duration::from_millis(5);
+    let schedule = |timers: &mut TestTimerRef, id, meta| {
+        timers.schedule_once_with_meta(delay, OneShotClosureState::new(id, |_| ()), meta);
+    };
+    schedule(&mut timers, single, TimerMeta::default());
+    schedule(&mut timers, tagged, meta("cancelled", 1));
+    schedule(&mut timers, owned, meta("kept", 2));
+    schedule(&mut timers, keyed, TimerMeta::default());
+
+    timers.cancel(&single);
+    timers.cancel_tag("cancelled");
+    timers.cancel_owner(2);
+    // replacing a timer with the same id
+    schedule(&mut timers, keyed, TimerMeta::default());
+    sync(&mut timers);
+
+    let ours = [single, tagged, owned, keyed];
+    let cancelled: Vec<_> = CANCELLED
+        .lock()
+        .unwrap()
+        .iter()
+        .copied()
+        .filter(|id| ours.contains(id))
+        .collect();
+    assert_eq!(cancelled, ours);
+}
+
+#[test]
+fn polled_timers_come_back_with_what_their_proc_returned() {
+    let queue = Mutex::new(DueQueue::new());
+    for (id, owner_gone) in [(1, false), (2, true), (3, false), (4, false)] {
+        queue.lock().unwrap().push(id, owner_gone);
+    }
+
+    // DM takes two records, skipping the one whose owner is gone
+    let mut records = Vec::new();
+    let taken = poll_queued(&queue, 2, |id, owner_gone| {
+        if !owner_gone {
+            records.push(id);
+        }
+        Ok::<_, ()>(!owner_gone)
+    });
+    assert_eq!(taken, Ok(2));
+    assert_eq!(records, [1, 3]);
+    assert_eq!(queue.lock().unwrap().len(), 1);
+
+    // then calls their procs and reports what they returned
+    let mut timers = RecordedTimers::default();
+    for (id, ret) in records.into_iter().zip([
+        TimerReturn::Cancel,
+        TimerReturn::Reschedule(NextFiring::Period),
+    ]) {
+        apply_periodic_return(&mut timers, id, ret);
+    }
+    assert_eq!(timers.0, [("cancel", 1, None)]);
+
+    assert_eq!(poll_queued(&queue, 10, |_, _| Ok::<_, ()>(true)), Ok(1));
+    assert!(queue.lock().unwrap().is_empty());
+}
@@ -30,19 +30,43 @@ use std::time::Duration;
 use channel::select;
 use crossbeam_channel as channel;
 use hierarchical_hash_wheel_timer::wheels::{cancellable::*, *};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::rc::Weak;
+use std::sync::Arc;
+use std::sync::atomic::{self, AtomicU64};
 use std::{cmp::Ordering, fmt, io, rc::Rc, thread, time::Instant};
 
 #[derive(Debug)]
 enum TimerMsg<I, O, P>
 where
     I: Hash + Clone + Eq,
-    O: OneshotState<Id = I>,
-    P: PeriodicState<Id = I>,
+    O: LateOneshotState<Id = I>,
+    P: AdaptivePeriodicState<Id = I>,
 {
-    Schedule(TimerEntry<I, O, P>),
+    Schedule(TimerEntry<I, O, P>, TimerMeta),
     Cancel(I),
+    Pause(I),
+    Resume(I),
+    Info(I, channel::Sender<Option<TimerInfo>>),
+    Reschedule {
+        id: I,
+        delay: Duration,
+        period: Option<Duration>,
+    },
+    Snooze(I, Duration),
+    CancelTag(String),
+    PauseTag(String),
+    ResumeTag(String),
+    ListTag(String, channel::Sender<Vec<I>>),
+    CancelOwner(u64),
+    ListOwner(u64, channel::Sender<Vec<I>>),
     Stop,
-    Tick,
+    /// Ticks the wheel, answering once the entries that came due have run
+    Tick(Option<channel::Sender<()>>),
+    TickBudget(Option<Duration>),
+    ClockJumpPolicy(ClockJumpPolicy, Duration),
+    ClockJumpHandler(fn(ClockJump)),
+    CancelHandler(fn(&I)),
 }
 
 /// A reference to a thread timer
@@ -53,8 +77,8 @@ where
 pub struct TimerRef<I, O, P>
 where
     I: Hash + Clone + Eq,
-    O: OneshotState<Id = I>,
-    P: PeriodicState<Id = I>,
+    O: LateOneshotState<Id = I>,
+    P: AdaptivePeriodicState<Id = I>,
 {
     work_queue: channel::Sender<TimerMsg<I, O, P>>,
 }
@@ -62,26 +86,618 @@ where
 /// Simple trait to implement tick() for
 pub trait TimerTicking {
     fn tick(&mut self);
+
+    /// Ticks and waits until the entries that came due on the tick have run, or were deferred by the tick budget.
+    fn tick_and_wait(&mut self) -> Result<(), channel::RecvTimeoutError>;
+
+    /// Limits how long each tick spends running due entries, the rest are deferred to the next tick.
+    /// Deferred entries run along with those due on the next tick, by priority and then in the order
+    /// they came due. `None` runs every due entry right away.
+    fn set_tick_budget(&mut self, budget: Option<Duration>);
 }
 
 impl<I, O, P> TimerTicking for TimerRef<I, O, P>
 where
     I: Hash + Clone + Eq,
-    O: OneshotState<Id = I>,
-    P: PeriodicState<Id = I>,
+    O: LateOneshotState<Id = I>,
+    P: AdaptivePeriodicState<Id = I>,
 {
     fn tick(&mut self) {
         self.work_queue
-            .send(TimerMsg::Tick)
+            .send(TimerMsg::Tick(None))
+            .unwrap_or_else(|e| log_error(format!("could not send Tick msg: {e:?}")));
+    }
+
+    fn tick_and_wait(&mut self) -> Result<(), channel::RecvTimeoutError> {
+        let (reply, response) = channel::bounded(1);
+        self.work_queue
+            .send(TimerMsg::Tick(Some(reply)))
             .unwrap_or_else(|e| log_error(format!("could not send Tick msg: {e:?}")));
+        response.recv_timeout(QUERY_TIMEOUT)
+    }
+
+    fn set_tick_budget(&mut self, budget: Option<Duration>) {
+        self.work_queue
+            .send(TimerMsg::TickBudget(budget))
+            .unwrap_or_else(|e| log_error(format!("could not send TickBudget msg: {e:?}")));
+    }
+}
+
+/// What the timer thread does when real time jumps ahead of the wheel,
+/// e.g. after the process was suspended or stopped in a debugger
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ClockJumpPolicy {
+    /// Catch up with real time, firing every timer that came due during the jump
+    #[default]
+    FireDue,
+    /// Push every timer back by the jump, as if it had not happened
+    Shift,
+}
+
+/// A jump of real time noticed by the timer thread
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockJump {
+    /// How far real time jumped ahead of the wheel
+    pub gap: Duration,
+    /// What was done about it
+    pub policy: ClockJumpPolicy,
+    /// How many timers fired while catching up, always 0 when shifting
+    pub fired: usize,
+}
+
+/// Trait for configuring how the timer thread deals with clock jumps, only autoticking timers notice them
+pub trait TimerClockJumps {
+    /// Sets what to do when real time jumps ahead by more than `threshold` at once.
+    fn set_clock_jump_policy(&mut self, policy: ClockJumpPolicy, threshold: Duration);
+
+    /// Sets a function to call on the timer thread after each clock jump.
+    fn on_clock_jump(&mut self, handler: fn(ClockJump));
+}
+
+impl<I, O, P> TimerClockJumps for TimerRef<I, O, P>
+where
+    I: Hash + Clone + Eq,
+    O: LateOneshotState<Id = I>,
+    P: AdaptivePeriodicState<Id = I>,
+{
+    fn set_clock_jump_policy(&mut self, policy: ClockJumpPolicy, threshold: Duration) {
+        self.work_queue
+            .send(TimerMsg::ClockJumpPolicy(policy, threshold))
+            .unwrap_or_else(|e| log_error(format!("could not send ClockJumpPolicy msg: {e:?}")));
+    }
+
+    fn on_clock_jump(&mut self, handler: fn(ClockJump)) {
+        self.work_queue
+            .send(TimerMsg::ClockJumpHandler(handler))
+            .unwrap_or_else(|e| log_error(format!("could not send ClockJumpHandler msg: {e:?}")));
+    }
+}
+
+/// Trait for hearing about every timer that gets cancelled
+pub trait TimerCancelling {
+    type Id;
+
+    /// Sets a function to call on the timer thread with the id of each cancelled timer, whether it was
+    /// cancelled on its own, by tag or by owner, or replaced by a timer with the same id.
+    fn on_cancel(&mut self, handler: fn(&Self::Id));
+}
+
+impl<I, O, P> TimerCancelling for TimerRef<I, O, P>
+where
+    I: Hash + Clone + Eq,
+    O: LateOneshotState<Id = I>,
+    P: AdaptivePeriodicState<Id = I>,
+{
+    type Id = I;
+
+    fn on_cancel(&mut self, handler: fn(&I)) {
+        self.work_queue
+            .send(TimerMsg::CancelHandler(handler))
+            .unwrap_or_else(|e| log_error(format!("could not send CancelHandler msg: {e:?}")));
+    }
+}
+
+/// Trait for suspending individual timers without losing their remaining time
+pub trait TimerPausing {
+    type Id;
+
+    /// Takes the timer out of the wheel, remembering how long it had left to run.
+    fn pause(&mut self, id: &Self::Id);
+
+    /// Puts a paused timer back into the wheel with the time it had left when paused.
+    fn resume(&mut self, id: &Self::Id);
+}
+
+impl<I, O, P> TimerPausing for TimerRef<I, O, P>
+where
+    I: Hash + Clone + Eq,
+    O: LateOneshotState<Id = I>,
+    P: AdaptivePeriodicState<Id = I>,
+{
+    type Id = I;
+
+    fn pause(&mut self, id: &Self::Id) {
+        self.work_queue
+            .send(TimerMsg::Pause(id.clone()))
+            .unwrap_or_else(|e| log_error(format!("could not send Pause msg: {e:?}")));
+    }
+
+    fn resume(&mut self, id: &Self::Id) {
+        self.work_queue
+            .send(TimerMsg::Resume(id.clone()))
+            .unwrap_or_else(|e| log_error(format!("could not send Resume msg: {e:?}")));
+    }
+}
+
+/// Trait for moving pending timers without giving them a new id
+pub trait TimerRescheduling {
+    type Id;
+
+    /// Makes the timer fire after `delay` from now, optionally changing the period of a periodic timer.
+    fn reschedule(&mut self, id: &Self::Id, delay: Duration, period: Option<Duration>);
+
+    /// Pushes the timer's next firing back by `delta`.
+    fn snooze(&mut self, id: &Self::Id, delta: Duration);
+}
+
+impl<I, O, P> TimerRescheduling for TimerRef<I, O, P>
+where
+    I: Hash + Clone + Eq,
+    O: LateOneshotState<Id = I>,
+    P: AdaptivePeriodicState<Id = I>,
+{
+    type Id = I;
+
+    fn reschedule(&mut self, id: &Self::Id, delay: Duration, period: Option<Duration>) {
+        let msg = TimerMsg::Reschedule {
+            id: id.clone(),
+            delay,
+            period,
+        };
+        self.work_queue
+            .send(msg)
+            .unwrap_or_else(|e| log_error(format!("could not send Reschedule msg: {e:?}")));
+    }
+
+    fn snooze(&mut self, id: &Self::Id, delta: Duration) {
+        self.work_queue
+            .send(TimerMsg::Snooze(id.clone(), delta))
+            .unwrap_or_else(|e| log_error(format!("could not send Snooze msg: {e:?}")));
+    }
+}
+
+/// Extra information the timer thread keeps about each timer
+#[derive(Debug, Clone, Default)]
+pub struct TimerMeta {
+    /// Groups timers so that they can be cancelled, paused, resumed or listed together
+    pub tag: Option<String>,
+    /// Identifies the object the timer's callback belongs to, see [`TimerOwners`]
+    pub owner: Option<u64>,
+    /// What to do when a timer with the same id is already pending or paused
+    pub duplicate: DuplicatePolicy,
+    /// Stops a periodic timer after it fired this many times
+    pub max_firings: Option<u32>,
+    /// Stops a periodic timer from firing once this much time has passed since it was scheduled
+    pub end_after: Option<Duration>,
+    /// Timers that come due on the same tick fire in order of descending priority
+    pub priority: i32,
+    /// What a periodic timer's period is measured from
+    pub period_mode: PeriodMode,
+    /// What a periodic timer does about firings it missed, `None` for the default of its [`PeriodMode`]
+    pub catch_up: Option<CatchUpPolicy>,
+    /// A one-shot timer firing later than this after its deadline is handed to
+    /// [`LateOneshotState::trigger_late`] instead of being triggered
+    pub max_lateness: Option<Duration>,
+}
+
+impl TimerMeta {
+    /// The catch-up policy of the timer, fixed-rate timers fire every missed occurrence by default
+    /// and fixed-delay timers fire once.
+    pub fn catch_up_policy(&self) -> CatchUpPolicy {
+        self.catch_up.unwrap_or(match self.period_mode {
+            PeriodMode::FixedDelay => CatchUpPolicy::FireOnce,
+            PeriodMode::FixedRate => CatchUpPolicy::FireAll,
+        })
+    }
+}
+
+/// What the period of a periodic timer is measured from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PeriodMode {
+    /// From when the callback finished, so slow callbacks push later firings back
+    #[default]
+    FixedDelay,
+    /// From when the timer should have fired, keeping to the timeline `start + n * period`
+    FixedRate,
+}
+
+/// What a periodic timer does when it fires so late that further occurrences are already due,
+/// e.g. after the timer thread fell behind
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CatchUpPolicy {
+    /// Fire every missed occurrence, one per tick, until the timer is back on schedule
+    FireAll,
+    /// Fire once, then carry on with the next occurrence that is not due yet
+    FireOnce,
+    /// Drop the late occurrence along with the missed ones and carry on with the next one that is not due yet
+    Skip,
+}
+
+/// How scheduling a timer treats an existing timer with the same id
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// Cancel the existing timer and schedule the new one
+    #[default]
+    Replace,
+    /// Keep the existing timer and drop the new one
+    KeepExisting,
+    /// Keep whichever of the two fires later
+    Extend,
+    /// Fire at most once per interval: the first timer fires right away, later ones replace the
+    /// pending timer's callback but keep its deadline, which is one interval after the last firing
+    Throttle(Duration),
+}
+
+/// Trait for scheduling timers with [`TimerMeta`] attached
+pub trait MetaTimer: Timer {
+    fn schedule_once_with_meta(
+        &mut self,
+        timeout: Duration,
+        state: Self::OneshotState,
+        meta: TimerMeta,
+    );
+
+    fn schedule_periodic_with_meta(
+        &mut self,
+        delay: Duration,
+        period: Duration,
+        state: Self::PeriodicState,
+        meta: TimerMeta,
+    );
+}
+
+impl<I, O, P> MetaTimer for TimerRef<I, O, P>
+where
+    I: Hash + Clone + Eq,
+    O: LateOneshotState<Id = I>,
+    P: AdaptivePeriodicState<Id = I>,
+{
+    fn schedule_once_with_meta(&mut self, timeout: Duration, state: O, meta: TimerMeta) {
+        let e = TimerEntry::OneShot { timeout, state };
+        self.work_queue
+            .send(TimerMsg::Schedule(e, meta))
+            .unwrap_or_else(|e| log_error(format!("could not send Schedule msg: {e:?}")));
+    }
+
+    fn schedule_periodic_with_meta(
+        &mut self,
+        delay: Duration,
+        period: Duration,
+        state: P,
+        meta: TimerMeta,
+    ) {
+        let e = TimerEntry::Periodic {
+            delay,
+            period,
+            state,
+        };
+        self.work_queue
+            .send(TimerMsg::Schedule(e, meta))
+            .unwrap_or_else(|e| log_error(format!("could not send Schedule msg: {e:?}")));
+    }
+}
+
+/// What a periodic timer does after it fired, unless it was cancelled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NextFiring {
+    /// Fire again after the timer's period
+    #[default]
+    Period,
+    /// Fire again after this delay, and after the period from then on
+    After(Duration),
+    /// Pause the timer, once resumed it fires again after its period
+    Pause,
+}
+
+/// Periodic timer state that can pick what happens after it fired
+pub trait AdaptivePeriodicState: PeriodicState {
+    /// What the timer does next, as decided by its last firing
+    fn next_firing(&self) -> NextFiring;
+
+    /// Adds occurrences the timer missed to report on its next firing, ignored by default.
+    fn add_skipped(&mut self, _skipped: u64) {}
+}
+
+impl<I> AdaptivePeriodicState for PeriodicClosureState<I>
+where
+    I: Hash + Clone + Eq,
+{
+    fn next_firing(&self) -> NextFiring {
+        NextFiring::Period
+    }
+}
+
+/// One-shot timer state that can tell when it fires too late, see [`TimerMeta::max_lateness`]
+pub trait LateOneshotState: OneshotState {
+    /// Called instead of [`OneshotState::trigger`] when the timer fires `lateness` past its deadline,
+    /// drops the timer without triggering it by default.
+    fn trigger_late(self, _lateness: Duration)
+    where
+        Self: Sized,
+    {
+    }
+}
+
+impl<I> LateOneshotState for OneShotClosureState<I> where I: Hash + Clone + Eq {}
+
+/// A one-shot timer state whose closure is also run when the timer fires too late,
+/// it gets passed the lateness in that case
+pub struct LateClosureState<I> {
+    id: I,
+    action: Box<dyn FnOnce(I, Option<Duration>) + Send + 'static>,
+}
+
+impl<I> LateClosureState<I> {
+    /// Produces a new instance of this state type from a unique id and the action to be executed
+    /// when it expires. The action gets passed `None` if the timer is on time, or how late it is
+    /// if that is more than its [max lateness](TimerMeta::max_lateness).
+    pub fn new<F>(id: I, action: F) -> Self
+    where
+        F: FnOnce(I, Option<Duration>) + Send + 'static,
+    {
+        LateClosureState {
+            id,
+            action: Box::new(action),
+        }
+    }
+}
+
+impl<I> OneshotState for LateClosureState<I>
+where
+    I: Hash + Clone + Eq,
+{
+    type Id = I;
+
+    fn id(&self) -> &Self::Id {
+        &self.id
+    }
+
+    fn trigger(self) {
+        (self.action)(self.id, None)
+    }
+}
+
+impl<I> LateOneshotState for LateClosureState<I>
+where
+    I: Hash + Clone + Eq,
+{
+    fn trigger_late(self, lateness: Duration) {
+        (self.action)(self.id, Some(lateness))
+    }
+}
+
+impl<I> fmt::Debug for LateClosureState<I>
+where
+    I: Hash + Clone + Eq + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "LateClosureState(id={:?}, action=<function>)", self.id)
+    }
+}
+
+/// A periodic timer state whose closure can return what happens after it fired
+pub struct AdaptiveClosureState<I> {
+    id: I,
+    next: NextFiring,
+    /// Missed occurrences since the last firing, see [`CatchUpPolicy`]
+    skipped: u32,
+    action: Box<dyn FnMut(I, u32) -> TimerReturn<NextFiring> + Send + 'static>,
+}
+
+impl<I> AdaptiveClosureState<I> {
+    /// Produces a new instance of this state type from a unique id and the action to be executed
+    /// every time it expires. The action returns the [`NextFiring`] of the timer.
+    pub fn new<F>(id: I, mut action: F) -> Self
+    where
+        F: FnMut(I) -> TimerReturn<NextFiring> + Send + 'static,
+    {
+        Self::reporting_skipped(id, move |id, _| action(id))
+    }
+
+    /// Like [`AdaptiveClosureState::new`], but the action is also passed how many occurrences
+    /// were skipped since it last ran.
+    pub fn reporting_skipped<F>(id: I, action: F) -> Self
+    where
+        F: FnMut(I, u32) -> TimerReturn<NextFiring> + Send + 'static,
+    {
+        AdaptiveClosureState {
+            id,
+            next: NextFiring::Period,
+            skipped: 0,
+            action: Box::new(action),
+        }
+    }
+}
+
+impl<I> PeriodicState for AdaptiveClosureState<I>
+where
+    I: Hash + Clone + Eq,
+{
+    type Id = I;
+
+    fn id(&self) -> &Self::Id {
+        &self.id
+    }
+
+    fn trigger(mut self) -> TimerReturn<Self>
+    where
+        Self: Sized,
+    {
+        let skipped = std::mem::take(&mut self.skipped);
+        (self.action)(self.id.clone(), skipped).map(|next| {
+            self.next = next;
+            self
+        })
+    }
+}
+
+impl<I> AdaptivePeriodicState for AdaptiveClosureState<I>
+where
+    I: Hash + Clone + Eq,
+{
+    fn next_firing(&self) -> NextFiring {
+        self.next
+    }
+
+    fn add_skipped(&mut self, skipped: u64) {
+        self.skipped = self
+            .skipped
+            .saturating_add(skipped.try_into().unwrap_or(u32::MAX));
+    }
+}
+
+impl<I> fmt::Debug for AdaptiveClosureState<I>
+where
+    I: Hash + Clone + Eq + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "AdaptiveClosureState(id={:?}, next={:?}, skipped={}, action=<function>)",
+            self.id, self.next, self.skipped
+        )
+    }
+}
+
+/// Trait for acting on every timer with a given [tag](TimerMeta::tag) at once
+pub trait TimerTagging {
+    type Id;
+
+    fn cancel_tag(&mut self, tag: &str);
+
+    fn pause_tag(&mut self, tag: &str);
+
+    fn resume_tag(&mut self, tag: &str);
+
+    /// Asks the timer thread for the ids of all pending or paused timers with the tag.
+    fn list_tag(&mut self, tag: &str) -> Result<Vec<Self::Id>, channel::RecvTimeoutError>;
+}
+
+impl<I, O, P> TimerTagging for TimerRef<I, O, P>
+where
+    I: Hash + Clone + Eq,
+    O: LateOneshotState<Id = I>,
+    P: AdaptivePeriodicState<Id = I>,
+{
+    type Id = I;
+
+    fn cancel_tag(&mut self, tag: &str) {
+        self.work_queue
+            .send(TimerMsg::CancelTag(tag.to_string()))
+            .unwrap_or_else(|e| log_error(format!("could not send CancelTag msg: {e:?}")));
+    }
+
+    fn pause_tag(&mut self, tag: &str) {
+        self.work_queue
+            .send(TimerMsg::PauseTag(tag.to_string()))
+            .unwrap_or_else(|e| log_error(format!("could not send PauseTag msg: {e:?}")));
+    }
+
+    fn resume_tag(&mut self, tag: &str) {
+        self.work_queue
+            .send(TimerMsg::ResumeTag(tag.to_string()))
+            .unwrap_or_else(|e| log_error(format!("could not send ResumeTag msg: {e:?}")));
+    }
+
+    fn list_tag(&mut self, tag: &str) -> Result<Vec<Self::Id>, channel::RecvTimeoutError> {
+        let (reply, response) = channel::bounded(1);
+        self.work_queue
+            .send(TimerMsg::ListTag(tag.to_string(), reply))
+            .unwrap_or_else(|e| log_error(format!("could not send ListTag msg: {e:?}")));
+        response.recv_timeout(QUERY_TIMEOUT)
+    }
+}
+
+/// Trait for acting on every timer belonging to the same [owner](TimerMeta::owner)
+pub trait TimerOwners {
+    type Id;
+
+    fn cancel_owner(&mut self, owner: u64);
+
+    /// Asks the timer thread for the ids of all pending or paused timers of the owner.
+    fn list_owner(&mut self, owner: u64) -> Result<Vec<Self::Id>, channel::RecvTimeoutError>;
+}
+
+impl<I, O, P> TimerOwners for TimerRef<I, O, P>
+where
+    I: Hash + Clone + Eq,
+    O: LateOneshotState<Id = I>,
+    P: AdaptivePeriodicState<Id = I>,
+{
+    type Id = I;
+
+    fn cancel_owner(&mut self, owner: u64) {
+        self.work_queue
+            .send(TimerMsg::CancelOwner(owner))
+            .unwrap_or_else(|e| log_error(format!("could not send CancelOwner msg: {e:?}")));
+    }
+
+    fn list_owner(&mut self, owner: u64) -> Result<Vec<Self::Id>, channel::RecvTimeoutError> {
+        let (reply, response) = channel::bounded(1);
+        self.work_queue
+            .send(TimerMsg::ListOwner(owner, reply))
+            .unwrap_or_else(|e| log_error(format!("could not send ListOwner msg: {e:?}")));
+        response.recv_timeout(QUERY_TIMEOUT)
+    }
+}
+
+/// How long to wait for the timer thread to answer a query.
+///
+/// The timer thread might be blocked on BYOND running one of its callbacks,
+/// which can't happen while BYOND itself is waiting for our answer.
+const QUERY_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// The state of a single timer, as reported by [`TimerQuerying::info`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimerInfo {
+    /// Time left until the timer fires next
+    pub remaining: Duration,
+    /// The period of a periodic timer, `None` for one-shot timers
+    pub period: Option<Duration>,
+    /// Whether the timer is currently paused
+    pub paused: bool,
+}
+
+/// Trait for looking up the state of timers from other threads
+pub trait TimerQuerying {
+    type Id;
+
+    /// Asks the timer thread about a timer, returning `None` if it is not pending or paused.
+    fn info(&mut self, id: &Self::Id) -> Result<Option<TimerInfo>, channel::RecvTimeoutError>;
+}
+
+impl<I, O, P> TimerQuerying for TimerRef<I, O, P>
+where
+    I: Hash + Clone + Eq,
+    O: LateOneshotState<Id = I>,
+    P: AdaptivePeriodicState<Id = I>,
+{
+    type Id = I;
+
+    fn info(&mut self, id: &Self::Id) -> Result<Option<TimerInfo>, channel::RecvTimeoutError> {
+        let (reply, response) = channel::bounded(1);
+        self.work_queue
+            .send(TimerMsg::Info(id.clone(), reply))
+            .unwrap_or_else(|e| log_error(format!("could not send Info msg: {e:?}")));
+        response.recv_timeout(QUERY_TIMEOUT)
     }
 }
 
 impl<I, O, P> Timer for TimerRef<I, O, P>
 where
     I: Hash + Clone + Eq,
-    O: OneshotState<Id = I>,
-    P: PeriodicState<Id = I>,
+    O: LateOneshotState<Id = I>,
+    P: AdaptivePeriodicState<Id = I>,
 {
     type Id = I;
     type OneshotState = O;
@@ -90,7 +706,7 @@ where
     fn schedule_once(&mut self, timeout: Duration, state: Self::OneshotState) {
         let e = TimerEntry::OneShot { timeout, state };
         self.work_queue
-            .send(TimerMsg::Schedule(e))
+            .send(TimerMsg::Schedule(e, TimerMeta::default()))
             .unwrap_or_else(|e| log_error(format!("could not send Schedule msg: {e:?}")));
     }
 
@@ -101,7 +717,7 @@ where
             state,
         };
         self.work_queue
-            .send(TimerMsg::Schedule(e))
+            .send(TimerMsg::Schedule(e, TimerMeta::default()))
             .unwrap_or_else(|e| log_error(format!("could not send Schedule msg: {e:?}")));
     }
 
@@ -114,8 +730,8 @@ where
 impl<I, O, P> Clone for TimerRef<I, O, P>
 where
     I: Hash + Clone + Eq,
-    O: OneshotState<Id = I>,
-    P: PeriodicState<Id = I>,
+    O: LateOneshotState<Id = I>,
+    P: AdaptivePeriodicState<Id = I>,
 {
     fn clone(&self) -> Self {
         Self {
@@ -129,8 +745,8 @@ where
 pub struct TimerWithThread<I, O, P>
 where
     I: Hash + Clone + Eq,
-    O: OneshotState<Id = I>,
-    P: PeriodicState<Id = I>,
+    O: LateOneshotState<Id = I>,
+    P: AdaptivePeriodicState<Id = I>,
 {
     timer_thread: thread::JoinHandle<()>,
     work_queue: channel::Sender<TimerMsg<I, O, P>>,
@@ -139,18 +755,23 @@ where
 impl<I, O, P> TimerWithThread<I, O, P>
 where
     I: Hash + Clone + Eq + fmt::Debug + Send + 'static,
-    O: OneshotState<Id = I> + fmt::Debug + Send + 'static,
-    P: PeriodicState<Id = I> + fmt::Debug + Send + 'static,
+    O: LateOneshotState<Id = I> + fmt::Debug + Send + 'static,
+    P: AdaptivePeriodicState<Id = I> + fmt::Debug + Send + 'static,
 {
     /// Create a new timer with its own thread.
     ///
     /// The thread will be called `"timer-thread"`.
     pub fn new() -> io::Result<TimerWithThread<I, O, P>> {
+        Self::with_time_source(TimeSource::System)
+    }
+
+    /// Create a new timer with its own thread, which reads real time from `time`.
+    pub fn with_time_source(time: TimeSource) -> io::Result<TimerWithThread<I, O, P>> {
         let (s, r) = channel::unbounded();
         let handle = thread::Builder::new()
             .name("timer-thread".to_string())
             .spawn(move || {
-                let timer = TimerThread::new(r);
+                let timer = TimerThread::new(r, time);
                 timer.run();
             })?;
         let twt = TimerWithThread {
@@ -215,8 +836,8 @@ where
 impl<I, O, P> fmt::Debug for TimerWithThread<I, O, P>
 where
     I: Hash + Clone + Eq,
-    O: OneshotState<Id = I>,
-    P: PeriodicState<Id = I>,
+    O: LateOneshotState<Id = I>,
+    P: AdaptivePeriodicState<Id = I>,
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "<TimerWithThread>")
@@ -234,6 +855,22 @@ impl
     pub fn for_uuid_closures_sans_autotick() -> io::Result<Self> {
         Self::new_sans_autotick()
     }
+
+    pub fn for_uuid_closures_with_time_source(time: TimeSource) -> io::Result<Self> {
+        Self::with_time_source(time)
+    }
+}
+
+impl TimerWithThread<uuid::Uuid, LateClosureState<uuid::Uuid>, AdaptiveClosureState<uuid::Uuid>> {
+    /// Shorthand for creating a timer instance using Uuid identifiers and closure state,
+    /// where one-shot closures learn when they fire too late and periodic closures can pick their next delay
+    pub fn for_uuid_adaptive_closures() -> io::Result<Self> {
+        Self::new()
+    }
+
+    pub fn for_uuid_adaptive_closures_sans_autotick() -> io::Result<Self> {
+        Self::new_sans_autotick()
+    }
 }
 
 /// Errors that can occur when stopping the timer thread
@@ -241,8 +878,8 @@ impl
 pub enum ThreadTimerError<I, O, P>
 where
     I: Hash + Clone + Eq,
-    O: OneshotState<Id = I>,
-    P: PeriodicState<Id = I>,
+    O: LateOneshotState<Id = I>,
+    P: AdaptivePeriodicState<Id = I>,
 {
     /// Sending of the `Stop` message failed
     CouldNotSendStopAsync,
@@ -259,23 +896,183 @@ where
 enum ThreadTimerEntry<I, O, P>
 where
     I: Hash + Clone + Eq,
-    O: OneshotState<Id = I>,
-    P: PeriodicState<Id = I>,
+    O: LateOneshotState<Id = I>,
+    P: AdaptivePeriodicState<Id = I>,
 {
-    OneShot { state: O },
-    Periodic { period: Duration, state: P },
+    OneShot {
+        state: O,
+        meta: TimerMeta,
+        seq: u64,
+        /// Wheel time at which the entry should fire
+        due: u64,
+    },
+    Periodic {
+        period: Duration,
+        state: P,
+        meta: TimerMeta,
+        limit: RepeatLimit,
+        seq: u64,
+        /// Wheel time at which the entry should fire next
+        due: u64,
+    },
+}
+
+/// Where an autoticking timer thread reads real time from
+#[derive(Debug, Clone, Default)]
+pub enum TimeSource {
+    /// The system's monotonic clock
+    #[default]
+    System,
+    /// A clock that only moves when it's told to, so that tests don't depend on how fast they run
+    Manual(ManualClock),
+}
+
+impl TimeSource {
+    fn now(&self) -> Instant {
+        match self {
+            TimeSource::System => Instant::now(),
+            TimeSource::Manual(clock) => clock.now(),
+        }
+    }
+}
+
+/// A clock that stands still until [`advance`](ManualClock::advance) moves it, see [`TimeSource::Manual`]
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    start: Instant,
+    elapsed_ms: Arc<AtomicU64>,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        ManualClock {
+            start: Instant::now(),
+            elapsed_ms: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        self.elapsed_ms
+            .fetch_add(by.as_millis() as u64, atomic::Ordering::AcqRel);
+    }
+
+    /// Time since the clock was created, as far as it has been advanced.
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_millis(self.elapsed_ms.load(atomic::Ordering::Acquire))
+    }
+
+    fn now(&self) -> Instant {
+        self.start + self.elapsed()
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The timer thread's view of time when an entry fires
+#[derive(Debug, Clone)]
+struct WheelClock {
+    /// Wheel time
+    now: u64,
+    /// Ticks left in the catch-up currently being ticked through
+    behind: u64,
+    /// When real time was last turned into ticks, `None` if the wheel is ticked by hand
+    checked_at: Option<Instant>,
+    time: TimeSource,
+}
+
+impl WheelClock {
+    /// Ticks the wheel still has to catch up with real time, including those spent since the clock was read
+    fn lag(&self) -> u64 {
+        let unchecked = self.checked_at.map_or(0, |t| {
+            self.time.now().saturating_duration_since(t).as_millis() as u64
+        });
+        self.behind + unchecked
+    }
+
+    /// How far real time is past `due` in wheel time.
+    fn lateness(&self, due: u64) -> u64 {
+        (self.now + self.lag()).saturating_sub(due)
+    }
+
+    /// How many occurrences after the one `due` in wheel time have already passed in real time.
+    fn missed(&self, due: u64, period: Duration) -> u64 {
+        match period.as_millis() as u64 {
+            0 => 0,
+            period => self.lateness(due) / period,
+        }
+    }
+
+    /// The delay until an occurrence `due` in wheel time, occurrences that are already due fire on the next tick.
+    fn until(&self, due: u64, period: Duration) -> Duration {
+        let delay = Duration::from_millis(due.saturating_sub(self.now));
+        // a zero period is left to be reported when inserting
+        delay.max(period.min(Duration::from_millis(1)))
+    }
 }
 
+/// When a periodic entry stops rescheduling itself
+#[derive(Debug, Clone, Copy, Default)]
+struct RepeatLimit {
+    /// How many more times the entry may fire
+    firings_left: Option<u32>,
+    /// Wheel time after which the entry may not fire anymore
+    end: Option<u64>,
+}
+
+impl RepeatLimit {
+    fn new(meta: &TimerMeta, now: u64) -> Self {
+        RepeatLimit {
+            firings_left: meta.max_firings,
+            end: meta.end_after.map(|d| now + d.as_millis() as u64),
+        }
+    }
+
+    /// Counts a firing and returns the limit for the next one, if the entry may fire again
+    /// after `delay` when it is `now`.
+    fn next(self, now: u64, delay: Duration) -> Option<Self> {
+        let firings_left = match self.firings_left {
+            Some(n) if n <= 1 => return None,
+            Some(n) => Some(n - 1),
+            None => None,
+        };
+        if self.is_over(now, delay) {
+            return None;
+        }
+        Some(RepeatLimit {
+            firings_left,
+            end: self.end,
+        })
+    }
+
+    /// Whether firing after `delay` when it is `now` would be past the end time.
+    fn is_over(&self, now: u64, delay: Duration) -> bool {
+        self.end
+            .is_some_and(|end| now + delay.as_millis() as u64 > end)
+    }
+}
+
+type EntryRef<I, O, P> = Rc<ThreadTimerEntry<I, O, P>>;
+
 impl<I, O, P> ThreadTimerEntry<I, O, P>
 where
     I: Hash + Clone + Eq + fmt::Debug,
-    O: OneshotState<Id = I> + fmt::Debug,
-    P: PeriodicState<Id = I> + fmt::Debug,
+    O: LateOneshotState<Id = I> + fmt::Debug,
+    P: AdaptivePeriodicState<Id = I> + fmt::Debug,
 {
-    fn from(e: TimerEntry<I, O, P>) -> (Self, Duration) {
+    /// Wraps a newly scheduled entry, `seq` orders it among entries that come due on the same tick.
+    fn from(e: TimerEntry<I, O, P>, meta: TimerMeta, now: u64, seq: u64) -> (Self, Duration) {
         match e {
             TimerEntry::OneShot { timeout, state } => {
-                let tte = ThreadTimerEntry::OneShot { state };
+                let tte = ThreadTimerEntry::OneShot {
+                    state,
+                    meta,
+                    seq,
+                    due: 0,
+                };
                 (tte, timeout)
             }
             TimerEntry::Periodic {
@@ -283,48 +1080,189 @@ where
                 period,
                 state,
             } => {
-                let tte = ThreadTimerEntry::Periodic { period, state };
+                let limit = RepeatLimit::new(&meta, now);
+                let tte = ThreadTimerEntry::Periodic {
+                    period,
+                    state,
+                    meta,
+                    limit,
+                    seq,
+                    due: 0,
+                };
                 (tte, delay)
             }
         }
     }
 
-    fn execute(self) -> Option<(Self, Duration)> {
+    fn meta(&self) -> &TimerMeta {
+        match self {
+            ThreadTimerEntry::OneShot { meta, .. } => meta,
+            ThreadTimerEntry::Periodic { meta, .. } => meta,
+        }
+    }
+
+    /// Position in scheduling order, which also holds for every later firing of a periodic entry
+    fn seq(&self) -> u64 {
+        match self {
+            ThreadTimerEntry::OneShot { seq, .. } => *seq,
+            ThreadTimerEntry::Periodic { seq, .. } => *seq,
+        }
+    }
+
+    fn period(&self) -> Option<Duration> {
         match self {
-            ThreadTimerEntry::OneShot { state } => {
-                state.trigger();
-                None
+            ThreadTimerEntry::OneShot { .. } => None,
+            ThreadTimerEntry::Periodic { period, .. } => Some(*period),
+        }
+    }
+
+    /// Replaces the period of a periodic entry, one-shot entries are returned unchanged.
+    fn with_period(self, new_period: Option<Duration>) -> Self {
+        match (self, new_period) {
+            (
+                ThreadTimerEntry::Periodic {
+                    state,
+                    meta,
+                    limit,
+                    seq,
+                    due,
+                    ..
+                },
+                Some(period),
+            ) => ThreadTimerEntry::Periodic {
+                period,
+                state,
+                meta,
+                limit,
+                seq,
+                due,
+            },
+            (e, _) => e,
+        }
+    }
+
+    /// Records when an entry inserted with `delay` should fire in wheel time.
+    fn set_due(&mut self, clock: &WheelClock, delay: Duration) {
+        match self {
+            ThreadTimerEntry::OneShot { due, .. } | ThreadTimerEntry::Periodic { due, .. } => {
+                *due = clock.now + delay.as_millis() as u64
             }
-            ThreadTimerEntry::Periodic { period, state } => match state.trigger() {
-                TimerReturn::Reschedule(new_state) => {
-                    let new_entry = ThreadTimerEntry::Periodic {
-                        period,
-                        state: new_state,
-                    };
-                    Some((new_entry, period))
+        }
+    }
+
+    /// Runs the entry, returning it again if it wants to be rescheduled,
+    /// or its metadata if it is done.
+    fn execute(self, clock: &WheelClock) -> Result<(Self, Duration), TimerMeta> {
+        match self {
+            ThreadTimerEntry::OneShot {
+                state, meta, due, ..
+            } => {
+                let lateness = Duration::from_millis(clock.lateness(due));
+                match meta.max_lateness {
+                    Some(max_lateness) if lateness > max_lateness => state.trigger_late(lateness),
+                    _ => state.trigger(),
                 }
-                TimerReturn::Cancel => None,
-            },
+                Err(meta)
+            }
+            ThreadTimerEntry::Periodic {
+                period,
+                mut state,
+                meta,
+                limit,
+                seq,
+                due,
+            } => {
+                // came due after its end time, which can be before its first firing
+                if limit.is_over(due, Duration::ZERO) {
+                    return Err(meta);
+                }
+                let missed = clock.missed(due, period);
+                let policy = meta.catch_up_policy();
+                // the next occurrence that is not due yet
+                let realigned = due + (missed + 1) * period.as_millis() as u64;
+                match policy {
+                    _ if missed == 0 => (),
+                    CatchUpPolicy::FireAll => (),
+                    CatchUpPolicy::FireOnce => state.add_skipped(missed),
+                    CatchUpPolicy::Skip => {
+                        state.add_skipped(missed + 1);
+                        let delay = clock.until(realigned, period);
+                        if limit.is_over(clock.now, delay) {
+                            return Err(meta);
+                        }
+                        let skipped_entry = ThreadTimerEntry::Periodic {
+                            period,
+                            state,
+                            meta,
+                            limit,
+                            seq,
+                            due: realigned,
+                        };
+                        return Ok((skipped_entry, delay));
+                    }
+                }
+                let TimerReturn::Reschedule(new_state) = state.trigger() else {
+                    return Err(meta);
+                };
+                let (due, delay) = match (new_state.next_firing(), meta.period_mode) {
+                    (NextFiring::After(delay), _) => {
+                        // a zero delay would make the entry expire on insertion
+                        let delay = delay.max(Duration::from_millis(1));
+                        (clock.now + delay.as_millis() as u64, delay)
+                    }
+                    // missed occurrences fire on the next ticks
+                    _ if missed > 0 && policy == CatchUpPolicy::FireAll => {
+                        let due = due + period.as_millis() as u64;
+                        (due, clock.until(due, period))
+                    }
+                    (_, PeriodMode::FixedDelay) => {
+                        // the callback's own run time shows up as lag
+                        let delay = period + Duration::from_millis(clock.lag());
+                        (clock.now + delay.as_millis() as u64, delay)
+                    }
+                    (_, PeriodMode::FixedRate) => (realigned, clock.until(realigned, period)),
+                };
+                let Some(limit) = limit.next(clock.now, delay) else {
+                    return Err(meta);
+                };
+                let new_entry = ThreadTimerEntry::Periodic {
+                    period,
+                    state: new_state,
+                    meta,
+                    limit,
+                    seq,
+                    due,
+                };
+                Ok((new_entry, delay))
+            }
         }
     }
 
-    fn execute_unique_ref(unique_ref: Rc<Self>) -> Option<(Rc<Self>, Duration)> {
-        let Ok(unique) = Rc::try_unwrap(unique_ref) else {
+    fn execute_unique_ref(
+        unique_ref: Rc<Self>,
+        clock: &WheelClock,
+    ) -> Result<(Self, Duration), TimerMeta> {
+        let unique = Rc::try_unwrap(unique_ref).map_err(|shared| {
             log_error("timer entry still had extra refs while executing");
-            return None;
-        };
-        unique.execute().map(|t| {
-            let (new_unique, delay) = t;
-            (Rc::new(new_unique), delay)
-        })
+            shared.meta().clone()
+        })?;
+        unique.execute(clock)
+    }
+
+    /// Whether the last firing of a periodic entry asked for it to be paused.
+    fn wants_pause(&self) -> bool {
+        match self {
+            ThreadTimerEntry::OneShot { .. } => false,
+            ThreadTimerEntry::Periodic { state, .. } => state.next_firing() == NextFiring::Pause,
+        }
     }
 }
 
 impl<I, O, P> CancellableTimerEntry for ThreadTimerEntry<I, O, P>
 where
     I: Hash + Clone + Eq + fmt::Debug,
-    O: OneshotState<Id = I> + fmt::Debug,
-    P: PeriodicState<Id = I> + fmt::Debug,
+    O: LateOneshotState<Id = I> + fmt::Debug,
+    P: AdaptivePeriodicState<Id = I> + fmt::Debug,
 {
     type Id = I;
 
@@ -339,31 +1277,136 @@ where
 struct TimerThread<I, O, P>
 where
     I: Hash + Clone + Eq + fmt::Debug,
-    O: OneshotState<Id = I> + fmt::Debug,
-    P: PeriodicState<Id = I> + fmt::Debug,
+    O: LateOneshotState<Id = I> + fmt::Debug,
+    P: AdaptivePeriodicState<Id = I> + fmt::Debug,
 {
     timer: QuadWheelWithOverflow<ThreadTimerEntry<I, O, P>>,
     work_queue: channel::Receiver<TimerMsg<I, O, P>>,
     running: bool,
     autoticking: bool,
+    /// Where real time is read from when autoticking
+    time: TimeSource,
     start: Instant,
     last_check: u128,
+    /// Wheel time in ms (or ticks) since the thread started, advanced by `tick` and `skip`.
+    now: u64,
+    /// Wheel time at which each entry currently in the wheel is due.
+    pending: HashMap<I, PendingEntry<I, O, P>>,
+    /// Entries taken out of the wheel by `Pause`, with the time they had left.
+    paused: HashMap<I, (ThreadTimerEntry<I, O, P>, Duration)>,
+    /// Ids of the pending and paused entries with each tag.
+    tags: GroupIndex<String, I>,
+    /// Ids of the pending and paused entries belonging to each owner.
+    owners: GroupIndex<u64, I>,
+    /// Wheel time until which each recently fired throttled timer may not fire again.
+    throttled_until: HashMap<I, u64>,
+    /// Sequence number for the next scheduled entry.
+    next_seq: u64,
+    /// Ticks the wheel has yet to catch up with, see [`TimerThread::catch_up`].
+    behind: u64,
+    /// What to do when real time jumps ahead by more than `jump_threshold` ticks.
+    jump_policy: ClockJumpPolicy,
+    jump_threshold: u64,
+    /// Told about every clock jump, see [`TimerClockJumps::on_clock_jump`].
+    jump_handler: Option<fn(ClockJump)>,
+    /// The clock jump being caught up with, and how many entries had fired before it.
+    catching_up: Option<(ClockJump, usize)>,
+    /// Told about every cancelled entry, see [`TimerCancelling::on_cancel`].
+    cancel_handler: Option<fn(&I)>,
+    /// How many entries have fired so far, to count those fired while catching up with a clock jump.
+    fired: usize,
+    /// How long a tick may spend running due entries, see [`TimerTicking::set_tick_budget`].
+    tick_budget: Option<Duration>,
+    /// Due entries that have not run yet, oldest first. Only non-empty between ticks if they
+    /// did not fit in the tick budget. They stay in `pending` until they run.
+    deferred: VecDeque<EntryRef<I, O, P>>,
+}
+
+/// Maps a key shared by several timers to the ids of those timers
+struct GroupIndex<K, I> {
+    groups: HashMap<K, HashSet<I>>,
+}
+
+impl<K, I> GroupIndex<K, I>
+where
+    K: Hash + Eq,
+    I: Hash + Clone + Eq,
+{
+    fn new() -> Self {
+        GroupIndex {
+            groups: HashMap::new(),
+        }
+    }
+
+    fn add(&mut self, key: K, id: I) {
+        self.groups.entry(key).or_default().insert(id);
+    }
+
+    fn remove(&mut self, key: &K, id: &I) {
+        if let Some(ids) = self.groups.get_mut(key) {
+            ids.remove(id);
+            if ids.is_empty() {
+                self.groups.remove(key);
+            }
+        }
+    }
+
+    fn take(&mut self, key: &K) -> HashSet<I> {
+        self.groups.remove(key).unwrap_or_default()
+    }
+
+    fn ids(&self, key: &K) -> Vec<I> {
+        self.groups
+            .get(key)
+            .map(|ids| ids.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+struct PendingEntry<I, O, P>
+where
+    I: Hash + Clone + Eq,
+    O: LateOneshotState<Id = I>,
+    P: AdaptivePeriodicState<Id = I>,
+{
+    entry: Weak<ThreadTimerEntry<I, O, P>>,
+    deadline: u64,
 }
 
 impl<I, O, P> TimerThread<I, O, P>
 where
     I: Hash + Clone + Eq + fmt::Debug,
-    O: OneshotState<Id = I> + fmt::Debug,
-    P: PeriodicState<Id = I> + fmt::Debug,
+    O: LateOneshotState<Id = I> + fmt::Debug,
+    P: AdaptivePeriodicState<Id = I> + fmt::Debug,
 {
-    fn new(work_queue: channel::Receiver<TimerMsg<I, O, P>>) -> TimerThread<I, O, P> {
+    fn new(
+        work_queue: channel::Receiver<TimerMsg<I, O, P>>,
+        time: TimeSource,
+    ) -> TimerThread<I, O, P> {
         TimerThread {
             timer: QuadWheelWithOverflow::new(),
             work_queue,
             running: true,
             autoticking: true,
-            start: Instant::now(),
+            start: time.now(),
+            time,
             last_check: 0u128,
+            now: 0,
+            pending: HashMap::new(),
+            paused: HashMap::new(),
+            tags: GroupIndex::new(),
+            owners: GroupIndex::new(),
+            throttled_until: HashMap::new(),
+            next_seq: 0,
+            behind: 0,
+            jump_policy: ClockJumpPolicy::default(),
+            jump_threshold: Self::DEFAULT_JUMP_THRESHOLD,
+            jump_handler: None,
+            catching_up: None,
+            cancel_handler: None,
+            fired: 0,
+            tick_budget: None,
+            deferred: VecDeque::new(),
         }
     }
 
@@ -373,8 +1416,25 @@ where
             work_queue,
             running: true,
             autoticking: false,
+            time: TimeSource::System,
             start: Instant::now(),
             last_check: 0u128,
+            now: 0,
+            pending: HashMap::new(),
+            paused: HashMap::new(),
+            tags: GroupIndex::new(),
+            owners: GroupIndex::new(),
+            throttled_until: HashMap::new(),
+            next_seq: 0,
+            behind: 0,
+            jump_policy: ClockJumpPolicy::default(),
+            jump_threshold: Self::DEFAULT_JUMP_THRESHOLD,
+            jump_handler: None,
+            catching_up: None,
+            cancel_handler: None,
+            fired: 0,
+            tick_budget: None,
+            deferred: VecDeque::new(),
         }
     }
 
@@ -382,17 +1442,22 @@ where
         while self.running {
             if self.autoticking {
                 let elap = self.elapsed();
-                if elap > 0 {
-                    for _ in 0..elap {
-                        self.tick();
-                    }
+                if elap > 0 || self.behind > 0 {
+                    self.advance(elap);
                 }
             }
 
             match self.work_queue.try_recv() {
                 Ok(msg) => self.handle_msg(msg),
+                // still catching up, so there's no time to wait
+                Err(channel::TryRecvError::Empty) if self.behind > 0 => (),
                 Err(channel::TryRecvError::Empty) => {
                     match self.timer.can_skip() {
+                        // a manual clock only moves when told to, so the time it moved is all taken in above
+                        // rather than partly counted as waited for
+                        Skip::Millis(_) if matches!(self.time, TimeSource::Manual(_)) => {
+                            thread::yield_now();
+                        }
                         Skip::None => {
                             thread::yield_now(); // try again after yielding for a bit
                         }
@@ -440,8 +1505,12 @@ where
         }
     }
 
-    /// Maximum number of catch-up ticks processed in a single `skip_and_tick` call.
-    const MAX_CATCH_UP_TICKS: u128 = 1_000;
+    /// Ticks of real time the wheel may fall behind at once before it counts as a clock jump.
+    const DEFAULT_JUMP_THRESHOLD: u64 = 1_000;
+
+    /// Maximum number of ticks run in a single `catch_up` call, so that messages are still handled
+    /// between them when the thread has a long way to catch up, e.g. after being suspended.
+    const MAX_CATCH_UP_TICKS: u64 = 1_000;
 
     #[inline(always)]
     fn skip_and_tick(&mut self, can_skip: u32, elapsed: u128) {
@@ -451,31 +1520,109 @@ where
             match elapsed.cmp(&can_skip_u128) {
                 Ordering::Greater => {
                     // took longer to get rescheduled than we wanted
-                    self.timer.skip(can_skip);
-                    let ticks = elapsed - can_skip_u128;
-                    // cap catch-up iterations per call to avoid starving the message
-                    // queue when the thread was suspended for a long time.
-                    let ticks = ticks.min(Self::MAX_CATCH_UP_TICKS);
-                    for _ in 0..ticks {
-                        self.tick();
-                    }
+                    self.skip(can_skip);
+                    self.advance(elapsed - can_skip_u128);
                 }
                 Ordering::Less => {
                     // we got woken up early, no need to tick
                     // Safety: elapsed < can_skip (u32), so elapsed fits in u32.
-                    self.timer.skip(elapsed as u32);
+                    self.skip(elapsed as u32);
                 }
                 Ordering::Equal => {
                     // elapsed == can_skip
-                    self.timer.skip(can_skip);
+                    self.skip(can_skip);
                 }
             }
         }
     }
 
+    /// Catches up with `ticks` of real time the wheel fell behind, applying the clock jump policy
+    /// if they are more than the jump threshold, e.g. after the process was suspended.
+    ///
+    /// A jump is reported once the wheel has caught up with it.
+    fn advance(&mut self, ticks: u128) {
+        if ticks > u128::from(self.jump_threshold) {
+            let jump = ClockJump {
+                gap: Duration::from_millis(ticks as u64),
+                policy: self.jump_policy,
+                fired: 0,
+            };
+            match self.jump_policy {
+                ClockJumpPolicy::FireDue => match &mut self.catching_up {
+                    // real time jumped again before the wheel caught up with the last jump
+                    Some((caught_up, _)) => caught_up.gap += jump.gap,
+                    None => self.catching_up = Some((jump, self.fired)),
+                },
+                // the wheel just doesn't move, so every entry keeps the time it had left
+                ClockJumpPolicy::Shift => {
+                    self.report_jump(jump);
+                    self.catch_up(0);
+                    return;
+                }
+            }
+        }
+        self.catch_up(ticks);
+        if self.behind > 0 {
+            return;
+        }
+        if let Some((mut jump, fired_before)) = self.catching_up.take() {
+            jump.fired = self.fired - fired_before;
+            self.report_jump(jump);
+        }
+    }
+
+    fn report_jump(&self, jump: ClockJump) {
+        if let Some(handler) = self.jump_handler {
+            handler(jump);
+        }
+    }
+
+    /// Moves the wheel `ticks` ahead along with the ticks it is still behind, keeping track of how far
+    /// behind real time it is meanwhile.
+    ///
+    /// Stretches without due entries are skipped rather than ticked through. At most
+    /// `MAX_CATCH_UP_TICKS` ticks are run, the rest are left in `behind` for the next call.
+    fn catch_up(&mut self, ticks: u128) {
+        let mut left = self.behind.saturating_add(ticks as u64);
+        let mut ticks_left = Self::MAX_CATCH_UP_TICKS;
+        while left > 0 && ticks_left > 0 {
+            let skippable = match self.timer.can_skip() {
+                _ if !self.deferred.is_empty() => 0,
+                Skip::Empty => left,
+                Skip::Millis(skip) => u64::from(skip).min(left),
+                Skip::None => 0,
+            };
+            if skippable > 0 {
+                let skip = skippable.min(u64::from(u32::MAX)) as u32;
+                self.skip(skip);
+                left -= u64::from(skip);
+            } else {
+                left -= 1;
+                ticks_left -= 1;
+                self.behind = left;
+                self.tick();
+            }
+        }
+        self.behind = left;
+    }
+
+    fn clock(&self) -> WheelClock {
+        let checked_at = self.start + Duration::from_millis(self.last_check as u64);
+        WheelClock {
+            now: self.now,
+            behind: self.behind,
+            checked_at: self.autoticking.then_some(checked_at),
+            time: self.time.clone(),
+        }
+    }
+
     #[inline(always)]
     fn elapsed(&mut self) -> u128 {
-        let elap = self.start.elapsed().as_millis();
+        let elap = self
+            .time
+            .now()
+            .saturating_duration_since(self.start)
+            .as_millis();
         let rel_elap = elap - self.last_check;
         self.last_check = elap;
         rel_elap
@@ -483,7 +1630,7 @@ where
 
     #[inline(always)]
     fn reset(&mut self) {
-        self.start = Instant::now();
+        self.start = self.time.now();
         self.last_check = 0;
     }
 
@@ -491,44 +1638,314 @@ where
     fn handle_msg(&mut self, msg: TimerMsg<I, O, P>) {
         match msg {
             TimerMsg::Stop => self.running = false,
-            TimerMsg::Tick => self.tick(),
-            TimerMsg::Schedule(entry) => {
-                let (e, delay) = ThreadTimerEntry::from(entry);
-                match self.timer.insert_ref_with_delay(Rc::new(e), delay) {
-                    Ok(_) => (), // ok
-                    Err(TimerError::Expired(e)) => {
-                        self.trigger_entry(e);
+            TimerMsg::Tick(done) => {
+                self.tick();
+                if let Some(done) = done {
+                    // the caller might have timed out already
+                    let _ = done.send(());
+                }
+            }
+            TimerMsg::TickBudget(budget) => self.tick_budget = budget,
+            TimerMsg::ClockJumpPolicy(policy, threshold) => {
+                self.jump_policy = policy;
+                self.jump_threshold = threshold.as_millis() as u64;
+            }
+            TimerMsg::ClockJumpHandler(handler) => self.jump_handler = Some(handler),
+            TimerMsg::CancelHandler(handler) => self.cancel_handler = Some(handler),
+            TimerMsg::Schedule(entry, meta) => {
+                let (mut e, delay) = ThreadTimerEntry::from(entry, meta, self.now, self.next_seq);
+                self.next_seq += 1;
+                // the delay counts from real time, which is ahead of the wheel while it catches up
+                let mut delay = delay + Duration::from_millis(self.behind);
+                if let (DuplicatePolicy::Throttle(_), Some(at)) =
+                    (e.meta().duplicate, self.deferred_position(e.id()))
+                {
+                    // came due but didn't fit in the tick budget, so the new callback waits its turn in its place
+                    self.cancel_entry(e.id());
+                    self.remember(&e);
+                    e.set_due(&self.clock(), Duration::ZERO);
+                    let e = Rc::new(e);
+                    let pending = PendingEntry {
+                        entry: Rc::downgrade(&e),
+                        deadline: self.now,
+                    };
+                    self.pending.insert(e.id().clone(), pending);
+                    self.deferred[at] = e;
+                    return;
+                }
+                if let Some(remaining) = self.remaining(e.id()) {
+                    match e.meta().duplicate {
+                        DuplicatePolicy::Replace => (),
+                        DuplicatePolicy::KeepExisting => return,
+                        DuplicatePolicy::Extend if remaining >= delay => return,
+                        DuplicatePolicy::Extend => (),
+                        DuplicatePolicy::Throttle(_) => delay = remaining,
                     }
-                    Err(f) => log_error(format!("could not insert timer entry: {f:?}")),
+                    self.cancel_entry(e.id());
+                } else if let DuplicatePolicy::Throttle(_) = e.meta().duplicate {
+                    let until = self.throttled_until.get(e.id()).copied().unwrap_or(0);
+                    delay = Duration::from_millis(until.saturating_sub(self.now));
                 }
+                self.remember(&e);
+                self.schedule_entry(e, delay);
             }
-            TimerMsg::Cancel(ref id) => match self.timer.cancel(id) {
-                Ok(_) => (),                     // ok
-                Err(TimerError::NotFound) => (), // also ok, might have been triggered already
-                Err(f) => log_error(format!("unexpected error cancelling timer: {f:?}")),
-            },
+            TimerMsg::Cancel(ref id) => self.cancel_entry(id),
+            TimerMsg::Pause(id) => self.pause_entry(id),
+            TimerMsg::Resume(ref id) => self.resume_entry(id),
+            TimerMsg::Reschedule { id, delay, period } => {
+                if let Some((e, _)) = self.paused.remove(&id) {
+                    // stays paused, but with the new time left
+                    self.paused.insert(id, (e.with_period(period), delay));
+                } else if let Some((e, _)) = self.take_entry(&id) {
+                    let delay = delay + Duration::from_millis(self.behind);
+                    self.schedule_entry(e.with_period(period), delay);
+                }
+            }
+            TimerMsg::Snooze(id, delta) => {
+                if let Some((_, remaining)) = self.paused.get_mut(&id) {
+                    *remaining += delta;
+                } else if let Some((e, remaining)) = self.take_entry(&id) {
+                    self.schedule_entry(e, remaining + delta);
+                }
+            }
+            TimerMsg::Info(ref id, reply) => {
+                // the caller might have timed out already
+                let _ = reply.send(self.info(id));
+            }
+            TimerMsg::CancelTag(ref tag) => {
+                for id in self.tags.take(tag) {
+                    self.cancel_entry(&id);
+                }
+            }
+            TimerMsg::PauseTag(ref tag) => {
+                for id in self.tags.ids(tag) {
+                    self.pause_entry(id);
+                }
+            }
+            TimerMsg::ResumeTag(ref tag) => {
+                for id in self.tags.ids(tag) {
+                    self.resume_entry(&id);
+                }
+            }
+            TimerMsg::ListTag(ref tag, reply) => {
+                // the caller might have timed out already
+                let _ = reply.send(self.tags.ids(tag));
+            }
+            TimerMsg::CancelOwner(ref owner) => {
+                for id in self.owners.take(owner) {
+                    self.cancel_entry(&id);
+                }
+            }
+            TimerMsg::ListOwner(ref owner, reply) => {
+                // the caller might have timed out already
+                let _ = reply.send(self.owners.ids(owner));
+            }
+        }
+    }
+
+    fn cancel_entry(&mut self, id: &I) {
+        if let Some(handler) = self.cancel_handler {
+            handler(id);
+        }
+        if let Some((e, _)) = self.paused.remove(id) {
+            self.forget(id, e.meta());
+            return;
+        }
+        if let Some(e) = self.pending.remove(id).and_then(|p| p.entry.upgrade()) {
+            self.forget(id, e.meta());
+        }
+        match self.timer.cancel(id) {
+            Ok(_) => (),                     // ok
+            Err(TimerError::NotFound) => (), // also ok, might have been triggered already
+            Err(f) => log_error(format!("unexpected error cancelling timer: {f:?}")),
+        }
+    }
+
+    fn pause_entry(&mut self, id: I) {
+        if let Some((e, remaining)) = self.take_entry(&id) {
+            self.paused.insert(id, (e, remaining));
+        } // otherwise: already fired, cancelled or paused
+    }
+
+    fn resume_entry(&mut self, id: &I) {
+        if let Some((e, remaining)) = self.paused.remove(id) {
+            self.schedule_entry(e, remaining);
+        }
+    }
+
+    /// Adds a newly scheduled entry to the indexes kept next to the wheel.
+    fn remember(&mut self, e: &ThreadTimerEntry<I, O, P>) {
+        let meta = e.meta();
+        if let Some(tag) = &meta.tag {
+            self.tags.add(tag.clone(), e.id().clone());
+        }
+        if let Some(owner) = meta.owner {
+            self.owners.add(owner, e.id().clone());
+        }
+    }
+
+    /// Removes an entry that is done for good from the indexes kept next to the wheel.
+    fn forget(&mut self, id: &I, meta: &TimerMeta) {
+        if let Some(tag) = &meta.tag {
+            self.tags.remove(tag, id);
+        }
+        if let Some(owner) = &meta.owner {
+            self.owners.remove(owner, id);
         }
     }
 
+    fn info(&self, id: &I) -> Option<TimerInfo> {
+        if let Some((e, remaining)) = self.paused.get(id) {
+            return Some(TimerInfo {
+                remaining: *remaining,
+                period: e.period(),
+                paused: true,
+            });
+        }
+        let pending = self.pending.get(id)?;
+        let e = pending.entry.upgrade()?;
+        Some(TimerInfo {
+            remaining: Duration::from_millis(pending.deadline.saturating_sub(self.now)),
+            period: e.period(),
+            paused: false,
+        })
+    }
+
+    /// Time left until a pending or paused entry fires, `None` if there is no such entry.
+    fn remaining(&self, id: &I) -> Option<Duration> {
+        self.info(id).map(|info| info.remaining)
+    }
+
+    /// Inserts an entry into the wheel, triggering it right away if it is already due.
+    fn schedule_entry(&mut self, mut e: ThreadTimerEntry<I, O, P>, delay: Duration) {
+        e.set_due(&self.clock(), delay);
+        match self.insert_entry(Rc::new(e), delay) {
+            Ok(_) => (), // ok
+            Err(TimerError::Expired(e)) => {
+                self.trigger_entry(e);
+            }
+            Err(f) => log_error(format!("could not insert timer entry: {f:?}")),
+        }
+    }
+
+    /// Inserts an entry into the wheel and records when it is due.
+    fn insert_entry(
+        &mut self,
+        e: EntryRef<I, O, P>,
+        delay: Duration,
+    ) -> Result<(), TimerError<EntryRef<I, O, P>>> {
+        let id = e.id().clone();
+        let pending = PendingEntry {
+            entry: Rc::downgrade(&e),
+            deadline: self.now + delay.as_millis() as u64,
+        };
+        self.timer.insert_ref_with_delay(e, delay)?;
+        self.pending.insert(id, pending);
+        Ok(())
+    }
+
+    /// Removes a pending entry from the wheel, returning it together with the time it had left.
+    /// Where a pending entry waits in `deferred`, if it came due but didn't fit in the tick budget.
+    fn deferred_position(&self, id: &I) -> Option<usize> {
+        let e = self.pending.get(id)?.entry.upgrade()?;
+        self.deferred.iter().position(|d| Rc::ptr_eq(d, &e))
+    }
+
+    fn take_entry(&mut self, id: &I) -> Option<(ThreadTimerEntry<I, O, P>, Duration)> {
+        let pending = self.pending.remove(id)?;
+        let e = pending.entry.upgrade()?;
+        // drop the wheel's (or the deferred queue's) own reference, so that ours is the only one left
+        if self.timer.cancel(id).is_err() {
+            let deferred = self.deferred.iter().position(|d| Rc::ptr_eq(d, &e))?;
+            self.deferred.remove(deferred);
+        }
+        let Ok(e) = Rc::try_unwrap(e) else {
+            log_error("timer entry still had extra refs while taking it out of the wheel");
+            return None;
+        };
+        let remaining = Duration::from_millis(pending.deadline.saturating_sub(self.now));
+        Some((e, remaining))
+    }
+
     fn trigger_entry(&mut self, e: Rc<ThreadTimerEntry<I, O, P>>) {
-        if let Some((new_e, delay)) = ThreadTimerEntry::execute_unique_ref(e) {
-            match self.timer.insert_ref_with_delay(new_e, delay) {
+        let id = e.id().clone();
+        if let DuplicatePolicy::Throttle(interval) = e.meta().duplicate {
+            self.throttle(id.clone(), interval);
+        }
+        self.fired += 1;
+        match ThreadTimerEntry::execute_unique_ref(e, &self.clock()) {
+            Ok((new_e, delay)) if new_e.wants_pause() => {
+                self.paused.insert(id, (new_e, delay));
+            }
+            Ok((new_e, delay)) => match self.insert_entry(Rc::new(new_e), delay) {
                 Ok(_) => (), // ok
                 Err(TimerError::Expired(e)) => {
                     log_error(format!(
                         "trying to insert periodic timer entry with 0ms period: {e:?}"
                     ));
+                    self.forget(&id, e.meta());
                 }
                 Err(f) => log_error(format!("could not insert timer entry: {f:?}")),
-            }
-        } // otherwise: timer is not rescheduled
+            },
+            // timer is not rescheduled
+            Err(meta) => self.forget(&id, &meta),
+        }
+    }
+
+    /// Maximum number of throttled timers remembered before expired ones are cleaned up.
+    const MAX_THROTTLED: usize = 1_024;
+
+    /// Remembers that a throttled timer fired, so that the next one waits for the interval.
+    fn throttle(&mut self, id: I, interval: Duration) {
+        if self.throttled_until.len() >= Self::MAX_THROTTLED {
+            let now = self.now;
+            self.throttled_until.retain(|_, until| *until > now);
+        }
+        self.throttled_until
+            .insert(id, self.now + interval.as_millis() as u64);
+    }
+
+    #[inline(always)]
+    fn skip(&mut self, amount: u32) {
+        self.timer.skip(amount);
+        self.now += amount as u64;
     }
 
     #[inline(always)]
     fn tick(&mut self) {
-        let res = self.timer.tick();
-        for e in res {
+        let mut res = self.timer.tick();
+        self.now += 1;
+        // the wheel's order within a tick is unspecified, so fall back to scheduling order
+        res.sort_by_key(|e| (std::cmp::Reverse(e.meta().priority), e.seq()));
+        self.deferred.extend(res);
+        // entries deferred from earlier ticks only go first among those of the same priority,
+        // the stable sort keeps them in the order they came due
+        self.deferred
+            .make_contiguous()
+            .sort_by_key(|e| std::cmp::Reverse(e.meta().priority));
+        self.run_deferred();
+    }
+
+    /// Runs due entries until the tick budget is used up, always running at least one.
+    fn run_deferred(&mut self) {
+        let start = Instant::now();
+        while let Some(e) = self.deferred.pop_front() {
+            let still_pending = self
+                .pending
+                .get(e.id())
+                .is_some_and(|p| Weak::ptr_eq(&p.entry, &Rc::downgrade(&e)));
+            if !still_pending {
+                // cancelled or replaced while it was deferred
+                continue;
+            }
+            self.pending.remove(e.id());
             self.trigger_entry(e);
+            if self
+                .tick_budget
+                .is_some_and(|budget| start.elapsed() >= budget)
+            {
+                break;
+            }
         }
     }
 }
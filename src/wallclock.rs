@@ -0,0 +1,83 @@
+//! How wall-clock timers wait for their deadlines, see [`crate::core::schedule_wall_clock_timer`].
+//!
+//! Kept apart from BYOND so that what a wall-clock timer does each time the wheel wakes it up can be tested on its own.
+use crate::timer::NextFiring;
+use hierarchical_hash_wheel_timer::TimerReturn;
+use std::time::Duration;
+
+/// Wall-clock timers further than this from their deadline check the system clock again halfway there.
+const WALL_CLOCK_RECHECK_MS: u64 = 1_000;
+
+/// How long a wall-clock timer waits with `remaining` milliseconds left until its deadline.
+pub fn wall_clock_wait(remaining: u64) -> Duration {
+    let wait = if remaining > WALL_CLOCK_RECHECK_MS {
+        remaining / 2
+    } else {
+        remaining
+    };
+    Duration::from_millis(wait.max(1))
+}
+
+/// What a wall-clock timer does when the wheel wakes it up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wakeup {
+    /// The deadline is still ahead, so the timer checks the clock again after this long
+    Wait(Duration),
+    /// The deadline passed this many milliseconds ago, so the timer fires
+    Due(u64),
+}
+
+/// The next deadline of a wall-clock timer, in UNIX milliseconds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deadline(pub u64);
+
+impl Deadline {
+    /// How long the timer waits before the wheel first wakes it up, a deadline in the past fires on the next tick.
+    pub fn first_wait(&self, now: u64) -> Duration {
+        wall_clock_wait(self.0.saturating_sub(now))
+    }
+
+    pub fn wake(&self, now: u64) -> Wakeup {
+        match self.0.checked_sub(now) {
+            Some(remaining) if remaining > 0 => Wakeup::Wait(wall_clock_wait(remaining)),
+            _ => Wakeup::Due(now - self.0),
+        }
+    }
+
+    /// Moves on to the `next` deadline after the timer fired at `now` and its proc returned `ret`.
+    ///
+    /// The timer keeps to its deadlines whatever delay the proc returned, and stops once the proc returns
+    /// a cancel or there is no next deadline. A pause pauses it with the next deadline already set,
+    /// so resuming it after that deadline fires it right away.
+    pub fn after_firing(
+        &mut self,
+        now: u64,
+        ret: TimerReturn<NextFiring>,
+        next: Option<u64>,
+    ) -> TimerReturn<NextFiring> {
+        match (ret, next) {
+            (TimerReturn::Reschedule(NextFiring::Pause), Some(next)) => {
+                self.0 = next;
+                TimerReturn::Reschedule(NextFiring::Pause)
+            }
+            (TimerReturn::Reschedule(_), Some(next)) => {
+                self.0 = next;
+                TimerReturn::Reschedule(NextFiring::After(self.first_wait(now)))
+            }
+            _ => TimerReturn::Cancel,
+        }
+    }
+}
+
+/// What the queued proc of a wall-clock timer returned, as applied to the timer, see [`crate::due::apply_periodic_return`].
+///
+/// The timer already waits for its next deadline, so a delay the proc returned is ignored
+/// rather than replacing that wait.
+pub fn wall_clock_return(ret: TimerReturn<NextFiring>) -> TimerReturn<NextFiring> {
+    match ret {
+        TimerReturn::Reschedule(NextFiring::After(_)) => {
+            TimerReturn::Reschedule(NextFiring::Period)
+        }
+        ret => ret,
+    }
+}